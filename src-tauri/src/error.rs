@@ -15,6 +15,18 @@ pub(crate) enum FluxyError {
     QRCode(#[from] QRCodeError),
     #[error(transparent)]
     Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error(transparent)]
+    Certificate(#[from] rcgen::Error),
+    #[error("文件传输服务未启动")]
+    ServerNotRunning,
+    #[error("未找到可用的网络地址")]
+    NoNetwork,
+    #[error("配置文件由更新版本的程序创建（版本 {0}），请升级后再修改配置")]
+    NewerSettings(u64),
+    #[error("配置项 {0} 无效")]
+    InvalidSettings(&'static str),
     #[cfg(target_os = "linux")]
     #[error(transparent)]
     EnvVar(#[from] std::env::VarError),
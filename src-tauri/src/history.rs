@@ -0,0 +1,374 @@
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tokio::fs::{self, OpenOptions};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+
+use crate::error::FluxyResult;
+use crate::lazy::APP_CONFIG_DIR;
+use crate::stream::mb_per_sec;
+
+const HISTORY_FILE: &str = "history.jsonl";
+
+lazy_static! {
+    /// 串行化对历史记录文件的读写
+    static ref HISTORY_LOCK: Mutex<()> = Mutex::new(());
+}
+
+/// 传输方向，以电脑为参照
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Direction {
+    /// 手机上传到电脑
+    Upload,
+    /// 手机从电脑下载
+    Download,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Outcome {
+    Completed,
+    /// 连接中断，上传的未完成文件会保留以便续传
+    Interrupted,
+    /// 桌面端取消
+    Cancelled,
+    /// 文件校验失败等错误
+    Failed,
+}
+
+/// 一条传输记录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct HistoryEntry {
+    /// 结束时间，unix 时间戳（毫秒）
+    time: u64,
+    direction: Direction,
+    name: String,
+    /// 上传时为保存路径，下载时为源文件路径
+    path: Option<PathBuf>,
+    size: u64,
+    hash: Option<String>,
+    peer: Option<IpAddr>,
+    /// 耗时（毫秒）
+    duration: u64,
+    /// 平均速度，MB/s
+    speed: f64,
+    outcome: Outcome,
+}
+
+/// 进行中的传输，结束时调用 [`Record::finish`] 写入历史记录
+#[derive(Debug, Clone)]
+pub(crate) struct Record {
+    direction: Direction,
+    name: String,
+    size: u64,
+    peer: Option<IpAddr>,
+    start: Instant,
+}
+
+impl Record {
+    pub(crate) fn new<S: Into<String>>(
+        direction: Direction,
+        name: S,
+        size: u64,
+        peer: Option<IpAddr>,
+    ) -> Self {
+        Self {
+            direction,
+            name: name.into(),
+            size,
+            peer,
+            start: Instant::now(),
+        }
+    }
+
+    /// 以 `start` 作为开始时间，用于分块上传等跨越多个请求的传输
+    pub(crate) fn started_at(mut self, start: Instant) -> Self {
+        self.start = start;
+        self
+    }
+
+    /// 写入历史记录，`bytes` 为本次实际传输的字节数，用于计算平均速度。
+    ///
+    /// 写入失败只记录日志，不影响传输结果。
+    pub(crate) async fn finish(
+        &self,
+        outcome: Outcome,
+        path: Option<&Path>,
+        hash: Option<&str>,
+        bytes: u64,
+    ) {
+        let cost = self.start.elapsed();
+
+        let entry = HistoryEntry {
+            time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64,
+            direction: self.direction,
+            name: self.name.clone(),
+            path: path.map(Path::to_path_buf),
+            size: self.size,
+            hash: hash.map(str::to_owned),
+            peer: self.peer,
+            duration: cost.as_millis() as u64,
+            speed: mb_per_sec(bytes, cost),
+            outcome,
+        };
+
+        if let Err(e) = append(&entry).await {
+            error!(message = "写入传输记录失败", entry = ?entry, error = ?e);
+        }
+    }
+}
+
+fn history_path() -> PathBuf {
+    APP_CONFIG_DIR.join(HISTORY_FILE)
+}
+
+async fn append(entry: &HistoryEntry) -> FluxyResult<()> {
+    let mut line = serde_json::to_vec(entry)?;
+    line.push(b'\n');
+
+    let _lock = HISTORY_LOCK.lock().await;
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(history_path())
+        .await?;
+    file.write_all(&line).await?;
+
+    Ok(())
+}
+
+/// 查询条件，为空的条件不参与过滤
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub(crate) struct HistoryFilter {
+    direction: Option<Direction>,
+    outcome: Option<Outcome>,
+    /// 文件名包含的关键字，不区分大小写
+    keyword: Option<String>,
+    /// 起始时间，unix 时间戳（毫秒）
+    since: Option<u64>,
+    /// 截止时间，unix 时间戳（毫秒）
+    until: Option<u64>,
+    offset: usize,
+    limit: Option<usize>,
+}
+
+impl HistoryFilter {
+    fn matches(&self, entry: &HistoryEntry, keyword: Option<&str>) -> bool {
+        self.direction.is_none_or(|d| d == entry.direction)
+            && self.outcome.is_none_or(|o| o == entry.outcome)
+            && self.since.is_none_or(|t| entry.time >= t)
+            && self.until.is_none_or(|t| entry.time <= t)
+            && keyword.is_none_or(|k| entry.name.to_lowercase().contains(k))
+    }
+}
+
+/// 按条件查询传输记录，最新的在前。
+///
+/// 无法解析的行会被跳过。
+pub(crate) async fn query(filter: &HistoryFilter) -> FluxyResult<Vec<HistoryEntry>> {
+    let content = {
+        let _lock = HISTORY_LOCK.lock().await;
+
+        match fs::read_to_string(history_path()).await {
+            Ok(c) => c,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => {
+                error!(message = "读取传输记录失败", error = ?e);
+                return Err(e.into());
+            }
+        }
+    };
+
+    Ok(filter_entries(&content, filter))
+}
+
+/// 从历史记录文件的内容中按条件筛选记录，最新的在前。
+fn filter_entries(content: &str, filter: &HistoryFilter) -> Vec<HistoryEntry> {
+    let keyword = filter.keyword.as_ref().map(|k| k.to_lowercase());
+
+    content
+        .lines()
+        .rev()
+        .filter_map(|line| match serde_json::from_str::<HistoryEntry>(line) {
+            Ok(entry) => Some(entry),
+            Err(e) => {
+                warn!(message = "跳过无法解析的传输记录", line = line, error = ?e);
+                None
+            }
+        })
+        .filter(|entry| filter.matches(entry, keyword.as_deref()))
+        .skip(filter.offset)
+        .take(filter.limit.unwrap_or(usize::MAX))
+        .collect()
+}
+
+/// 将符合条件的传输记录导出为 JSON 文件，返回导出的记录数。
+pub(crate) async fn export(path: &Path, filter: &HistoryFilter) -> FluxyResult<usize> {
+    let entries = query(filter).await?;
+
+    fs::write(path, serde_json::to_vec_pretty(&entries)?)
+        .await
+        .map_err(|e| {
+            error!(message = "导出传输记录失败", path = ?path, error = ?e);
+            e
+        })?;
+
+    info!(message = "已导出传输记录", path = ?path, count = entries.len());
+
+    Ok(entries.len())
+}
+
+/// 删除所有传输记录。
+pub(crate) async fn clear() -> FluxyResult<()> {
+    let _lock = HISTORY_LOCK.lock().await;
+
+    match fs::remove_file(history_path()).await {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => {
+            error!(message = "清空传输记录失败", error = ?e);
+            return Err(e.into());
+        }
+    }
+
+    info!("已清空传输记录");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn entry(time: u64, direction: Direction, name: &str, outcome: Outcome) -> HistoryEntry {
+        HistoryEntry {
+            time,
+            direction,
+            name: name.to_string(),
+            path: None,
+            size: 1024,
+            hash: None,
+            peer: None,
+            duration: 10,
+            speed: 0.1,
+            outcome,
+        }
+    }
+
+    fn names(entries: &[HistoryEntry]) -> Vec<&str> {
+        entries.iter().map(|e| e.name.as_str()).collect()
+    }
+
+    #[test]
+    fn match_each_field() {
+        let e = entry(1000, Direction::Upload, "Photo.JPG", Outcome::Completed);
+        let matches = |filter: HistoryFilter| {
+            let keyword = filter.keyword.as_ref().map(|k| k.to_lowercase());
+            filter.matches(&e, keyword.as_deref())
+        };
+
+        assert!(matches(HistoryFilter::default()));
+
+        assert!(matches(HistoryFilter {
+            direction: Some(Direction::Upload),
+            ..Default::default()
+        }));
+        assert!(!matches(HistoryFilter {
+            direction: Some(Direction::Download),
+            ..Default::default()
+        }));
+
+        assert!(matches(HistoryFilter {
+            outcome: Some(Outcome::Completed),
+            ..Default::default()
+        }));
+        assert!(!matches(HistoryFilter {
+            outcome: Some(Outcome::Failed),
+            ..Default::default()
+        }));
+
+        // 关键字不区分大小写
+        assert!(matches(HistoryFilter {
+            keyword: Some("photo.jpg".to_string()),
+            ..Default::default()
+        }));
+        assert!(!matches(HistoryFilter {
+            keyword: Some("video".to_string()),
+            ..Default::default()
+        }));
+
+        // 起止时间都包含边界
+        assert!(matches(HistoryFilter {
+            since: Some(1000),
+            until: Some(1000),
+            ..Default::default()
+        }));
+        assert!(!matches(HistoryFilter {
+            since: Some(1001),
+            ..Default::default()
+        }));
+        assert!(!matches(HistoryFilter {
+            until: Some(999),
+            ..Default::default()
+        }));
+    }
+
+    #[test]
+    fn filter_jsonl() {
+        let mut content = String::new();
+        for (i, name) in ["a.txt", "b.txt", "c.txt", "d.txt"].iter().enumerate() {
+            let direction = if i % 2 == 0 {
+                Direction::Upload
+            } else {
+                Direction::Download
+            };
+            let e = entry(i as u64, direction, name, Outcome::Completed);
+            content.push_str(&serde_json::to_string(&e).unwrap());
+            content.push('\n');
+
+            if i == 1 {
+                // 写入中断留下的不完整行和格式错误的行
+                content.push_str("{\"time\": 5, \"direction\"\n");
+                content.push_str(&json!({ "time": "x" }).to_string());
+                content.push('\n');
+            }
+        }
+
+        let all = filter_entries(&content, &HistoryFilter::default());
+        assert_eq!(names(&all), ["d.txt", "c.txt", "b.txt", "a.txt"]);
+
+        let page = filter_entries(
+            &content,
+            &HistoryFilter {
+                offset: 1,
+                limit: Some(2),
+                ..Default::default()
+            },
+        );
+        assert_eq!(names(&page), ["c.txt", "b.txt"]);
+
+        // 先筛选再分页
+        let uploads = filter_entries(
+            &content,
+            &HistoryFilter {
+                direction: Some(Direction::Upload),
+                offset: 1,
+                ..Default::default()
+            },
+        );
+        assert_eq!(names(&uploads), ["a.txt"]);
+
+        assert!(filter_entries("", &HistoryFilter::default()).is_empty());
+    }
+}
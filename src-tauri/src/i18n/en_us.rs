@@ -16,11 +16,17 @@ pub(super) const EN_US: &Translations = &Translations {
     qrcode_page_url_tooltip: "Copy Link to Clipboard",
     qrcode_page_url_copied_message: "Link Copied",
     qrcode_page_toast_message: "Please scan this QR code with your phone",
+    qrcode_page_fingerprint_label: "Certificate Fingerprint",
     ok_button_text: "Confirm",
     clear_button_text: "Clear File List",
     send_page_title: "Send Files",
     send_page_empty_drop_description: "Drag files here",
     send_page_drop_description: "You can continue dragging more files",
+    send_page_connected_description:
+        "A device is connected. You can change the files and create a new QR code for another device",
+    send_page_new_qrcode_button_text: "New QR Code for Another Device",
+    send_page_download_completed_label: "Downloaded",
+    send_page_download_interrupted_label: "Download interrupted",
     list_item_file_size_label: "Size",
     list_item_file_type_label: "Type",
     send_page_list_item_tooltip: "Click to Preview File",
@@ -30,4 +36,12 @@ pub(super) const EN_US: &Translations = &Translations {
     receive_page_dropdown_pick_button_label: "Change",
     receive_page_directory_path_label: "Save Directory",
     receive_page_directory_path_tooltip: "Click to Open Directory in File Explorer",
+    receive_page_pause_tooltip: "Pause",
+    receive_page_resume_tooltip: "Resume",
+    receive_page_cancel_tooltip: "Cancel",
+    approval_dialog_title: "Accept This File?",
+    approval_dialog_sender_label: "Sender",
+    approval_accept_button_text: "Accept",
+    approval_always_allow_button_text: "Always Allow This Device",
+    approval_reject_button_text: "Reject",
 };
@@ -1,6 +1,6 @@
 use std::{collections::HashMap, sync::LazyLock};
 
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 mod en_us;
 mod zh_cn;
@@ -12,9 +12,11 @@ pub static LOCALES: LazyLock<HashMap<&'static Locale, &Translations>> = LazyLock
         .collect()
 });
 
-#[derive(PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Locale {
+    #[serde(rename = "zh-CN")]
     ZhCN,
+    #[serde(rename = "en-US")]
     EnUS,
 }
 
@@ -53,11 +55,16 @@ pub struct Translations {
     pub qrcode_page_url_tooltip: &'static str,
     pub qrcode_page_url_copied_message: &'static str,
     pub qrcode_page_toast_message: &'static str,
+    pub qrcode_page_fingerprint_label: &'static str,
     pub ok_button_text: &'static str,
     pub clear_button_text: &'static str,
     pub send_page_title: &'static str,
     pub send_page_empty_drop_description: &'static str,
     pub send_page_drop_description: &'static str,
+    pub send_page_connected_description: &'static str,
+    pub send_page_new_qrcode_button_text: &'static str,
+    pub send_page_download_completed_label: &'static str,
+    pub send_page_download_interrupted_label: &'static str,
     pub list_item_file_size_label: &'static str,
     pub list_item_file_type_label: &'static str,
     pub send_page_list_item_tooltip: &'static str,
@@ -67,4 +74,12 @@ pub struct Translations {
     pub receive_page_dropdown_pick_button_label: &'static str,
     pub receive_page_directory_path_label: &'static str,
     pub receive_page_directory_path_tooltip: &'static str,
+    pub receive_page_pause_tooltip: &'static str,
+    pub receive_page_resume_tooltip: &'static str,
+    pub receive_page_cancel_tooltip: &'static str,
+    pub approval_dialog_title: &'static str,
+    pub approval_dialog_sender_label: &'static str,
+    pub approval_accept_button_text: &'static str,
+    pub approval_always_allow_button_text: &'static str,
+    pub approval_reject_button_text: &'static str,
 }
@@ -16,11 +16,16 @@ pub(super) const ZH_CN: &Translations = &Translations {
     qrcode_page_url_tooltip: "复制链接到剪贴板",
     qrcode_page_url_copied_message: "已复制链接",
     qrcode_page_toast_message: "请使用手机扫描此二维码",
+    qrcode_page_fingerprint_label: "证书指纹",
     ok_button_text: "确认",
     clear_button_text: "清空文件列表",
     send_page_title: "发送文件",
     send_page_empty_drop_description: "将文件拖到此处",
     send_page_drop_description: "可继续拖入文件",
+    send_page_connected_description: "已有设备连接，可修改文件后为其他设备生成新的二维码",
+    send_page_new_qrcode_button_text: "为其他设备生成二维码",
+    send_page_download_completed_label: "已下载",
+    send_page_download_interrupted_label: "下载中断",
     list_item_file_size_label: "大小",
     list_item_file_type_label: "类型",
     send_page_list_item_tooltip: "单击预览文件",
@@ -30,4 +35,12 @@ pub(super) const ZH_CN: &Translations = &Translations {
     receive_page_dropdown_pick_button_label: "修改",
     receive_page_directory_path_label: "保存目录",
     receive_page_directory_path_tooltip: "单击在文件管理器中打开此目录",
+    receive_page_pause_tooltip: "暂停",
+    receive_page_resume_tooltip: "继续",
+    receive_page_cancel_tooltip: "取消",
+    approval_dialog_title: "是否接收此文件？",
+    approval_dialog_sender_label: "发送端",
+    approval_accept_button_text: "接收",
+    approval_always_allow_button_text: "始终允许此设备",
+    approval_reject_button_text: "拒绝",
 };
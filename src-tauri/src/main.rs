@@ -2,81 +2,92 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 mod error;
+mod history;
 mod i18n;
 mod lazy;
 #[cfg(target_os = "linux")]
 mod linux;
 #[cfg(target_os = "macos")]
 mod menu;
+mod network;
 mod server;
+mod settings;
 mod stream;
+mod throttle;
 
 #[macro_use]
 extern crate lazy_static;
 #[macro_use]
 extern crate tracing;
 
-use std::{
-    path::PathBuf,
-    time::{Duration, SystemTime, UNIX_EPOCH},
-};
+use std::path::{Path, PathBuf};
 
 use qrcode_generator::QrCodeEcc;
 use serde::Serialize;
 use sys_locale::get_locale;
 use tauri::{AppHandle, Manager, UpdaterEvent};
 use time::macros::{format_description, offset};
-use tokio::fs::File;
+use tokio::fs::{self, File};
 use tracing::Level;
 use tracing_subscriber::fmt::time::OffsetTime;
 
+use crate::history::{HistoryEntry, HistoryFilter};
 use crate::i18n::{Locale, Translations, LOCALES};
-use crate::lazy::LOCAL_IP;
 #[cfg(target_os = "macos")]
 use crate::menu::{handle_menu_event, new_menu};
-use crate::server::{SendFile, DOWNLOADS_DIR, MAIN_WINDOW, QR_CODE_MAP, SEND_FILES};
-use crate::{error::FluxyResult, lazy::APP_CONFIG_DIR};
-
-fn now() -> FluxyResult<Duration> {
-    SystemTime::now().duration_since(UNIX_EPOCH).map_err(|e| {
-        error!(message = "获取时间出错", error = ?e);
-        e.into()
-    })
-}
-
-enum Mode {
-    Send,
-    Receive,
-}
-
-impl Mode {
-    fn to_str(&self) -> &'static str {
-        match self {
-            Mode::Send => "send",
-            Mode::Receive => "receive",
-        }
-    }
-}
+use crate::network::NetworkInterface;
+use crate::server::{
+    approval, quota, session, transfer, ConflictPolicy, Mode, SendFile, SessionInfo,
+    CONFLICT_POLICY, DOWNLOADS_DIR, MAIN_WINDOW,
+};
+use crate::settings::{Settings, SETTINGS};
+use crate::{
+    error::{FluxyError, FluxyResult},
+    lazy::APP_CONFIG_DIR,
+};
 
 #[derive(Debug, Serialize)]
 struct QrCode {
     svg: String,
     url: String,
     id: u64,
+    /// 启用 HTTPS 时的证书指纹
+    fingerprint: Option<&'static str>,
 }
 
 impl QrCode {
-    fn new(mode: Mode) -> FluxyResult<Self> {
-        let ts = now()?.as_secs();
-        debug!(message = "获取到时间戳", ts = ts);
-
-        let url = format!(
-            "http://{}:{}/connect?mode={}&ts={}",
-            *LOCAL_IP,
-            5800,
-            mode.to_str(),
-            ts
+    async fn new(mode: Mode, files: Vec<SendFile>) -> FluxyResult<Self> {
+        let port = server::port().ok_or_else(|| {
+            error!("文件传输服务未启动，无法创建二维码");
+            FluxyError::ServerNotRunning
+        })?;
+
+        let host = network::current_host().await.ok_or_else(|| {
+            error!("未找到可用的网络地址，无法创建二维码");
+            FluxyError::NoNetwork
+        })?;
+
+        let (id, token) = session::create(mode, files).await;
+
+        let fingerprint = server::fingerprint();
+        let scheme = if fingerprint.is_some() {
+            "https"
+        } else {
+            "http"
+        };
+
+        let mut url = format!(
+            "{}://{}:{}/connect?mode={}&ts={}&token={}",
+            scheme,
+            host,
+            port,
+            mode.as_str(),
+            id,
+            token
         );
+        if let Some(fp) = fingerprint {
+            url = url + "&fp=" + fp;
+        }
         debug!(message = "二维码信息", url = url);
 
         let code = qrcode_generator::to_svg_to_string(&url, QrCodeEcc::Low, 256, None::<&str>)
@@ -90,7 +101,8 @@ impl QrCode {
         Ok(Self {
             svg: code,
             url,
-            id: ts,
+            id,
+            fingerprint,
         })
     }
 }
@@ -99,8 +111,7 @@ impl QrCode {
 async fn get_qr_code_state(id: u64) -> bool {
     trace!("获取 server 地址二维码状态");
 
-    let map = QR_CODE_MAP.read().await;
-    let state = map.contains_key(&id);
+    let state = session::is_connected(id).await;
 
     info!(message = "server 地址二维码可用状态", state = !state);
 
@@ -111,7 +122,7 @@ async fn get_qr_code_state(id: u64) -> bool {
 async fn upload_qr_code() -> FluxyResult<QrCode> {
     trace!("获取上传地址二维码");
 
-    let code = QrCode::new(Mode::Send)?;
+    let code = QrCode::new(Mode::Send, Vec::new()).await?;
 
     info!(
         message = "上传地址二维码已创建",
@@ -125,10 +136,8 @@ async fn upload_qr_code() -> FluxyResult<QrCode> {
 #[tauri::command]
 async fn get_send_files_url_qr_code(files: Vec<SendFile>) -> FluxyResult<QrCode> {
     trace!("获取发送址二维码");
-    let mut send_files = SEND_FILES.write().await;
-    *send_files = Some(files);
 
-    let code = QrCode::new(Mode::Receive)?;
+    let code = QrCode::new(Mode::Receive, files).await?;
 
     info!(
         message = "发送地址二维码已创建",
@@ -139,6 +148,62 @@ async fn get_send_files_url_qr_code(files: Vec<SendFile>) -> FluxyResult<QrCode>
     Ok(code)
 }
 
+#[tauri::command]
+async fn sessions() -> Vec<SessionInfo> {
+    trace!("获取会话列表");
+
+    session::sessions().await
+}
+
+#[tauri::command]
+fn cancel_transfer(id: u64) -> bool {
+    trace!("取消传输");
+
+    transfer::cancel(id)
+}
+
+#[tauri::command]
+fn pause_transfer(id: u64) -> bool {
+    trace!("暂停传输");
+
+    transfer::pause(id)
+}
+
+#[tauri::command]
+fn resume_transfer(id: u64) -> bool {
+    trace!("恢复传输");
+
+    transfer::resume(id)
+}
+
+#[tauri::command]
+async fn respond_upload_approval(id: u64, accept: bool, always_allow: bool) -> bool {
+    trace!("处理上传确认");
+
+    approval::respond(id, accept, always_allow).await
+}
+
+#[tauri::command]
+async fn transfer_history(filter: HistoryFilter) -> FluxyResult<Vec<HistoryEntry>> {
+    trace!("查询传输记录");
+
+    history::query(&filter).await
+}
+
+#[tauri::command]
+async fn export_history(path: PathBuf, filter: HistoryFilter) -> FluxyResult<usize> {
+    trace!("导出传输记录");
+
+    history::export(&path, &filter).await
+}
+
+#[tauri::command]
+async fn clear_history() -> FluxyResult<()> {
+    trace!("清空传输记录");
+
+    history::clear().await
+}
+
 #[tauri::command]
 async fn downloads_dir() -> PathBuf {
     trace!("获取下载目录");
@@ -151,13 +216,197 @@ async fn downloads_dir() -> PathBuf {
 }
 
 #[tauri::command]
-async fn change_downloads_dir(path: PathBuf) {
+async fn change_downloads_dir(path: PathBuf) -> FluxyResult<()> {
     trace!("修改下载目录");
 
-    let mut downloads_dir = DOWNLOADS_DIR.write().await;
-    downloads_dir.clone_from(&path);
+    let settings = settings::update(|s| s.downloads_dir.clone_from(&path)).await?;
+    apply_settings(&settings).await;
 
     info!(message = "下载目录已修改", dir = ?path);
+
+    Ok(())
+}
+
+#[tauri::command]
+async fn conflict_policy() -> ConflictPolicy {
+    trace!("获取同名文件处理方式");
+
+    let policy = *CONFLICT_POLICY.read().await;
+
+    info!(message = "当前同名文件处理方式为", policy = ?policy);
+
+    policy
+}
+
+#[tauri::command]
+async fn change_conflict_policy(policy: ConflictPolicy) -> FluxyResult<()> {
+    trace!("修改同名文件处理方式");
+
+    let settings = settings::update(|s| s.conflict_policy = policy).await?;
+    apply_settings(&settings).await;
+
+    info!(message = "同名文件处理方式已修改", policy = ?policy);
+
+    Ok(())
+}
+
+#[tauri::command]
+fn network_interfaces() -> Vec<NetworkInterface> {
+    trace!("获取网卡列表");
+
+    network::interfaces()
+}
+
+#[tauri::command]
+async fn change_preferred_interface(name: Option<String>) -> FluxyResult<()> {
+    trace!("修改首选网卡");
+
+    settings::update(|s| s.preferred_interface.clone_from(&name)).await?;
+    network::check_change().await;
+
+    info!(message = "首选网卡已修改", name = ?name);
+
+    Ok(())
+}
+
+/// 将配置应用到服务端，端口在重启后生效。
+async fn apply_settings(settings: &Settings) {
+    DOWNLOADS_DIR
+        .write()
+        .await
+        .clone_from(&settings.downloads_dir);
+    *CONFLICT_POLICY.write().await = settings.conflict_policy;
+    session::set_timeouts(settings.qr_code_ttl, settings.idle_timeout);
+    approval::set_required(settings.require_upload_approval);
+    quota::set_limits(
+        settings.disk_space_margin,
+        settings.max_file_size,
+        settings.max_session_size,
+    );
+    stream::set_progress_interval(settings.progress_interval);
+    throttle::set_limits(settings.bandwidth_limit, settings.transfer_bandwidth_limit);
+}
+
+#[tauri::command]
+async fn change_bandwidth_limit(global: Option<u64>, per_transfer: Option<u64>) -> FluxyResult<()> {
+    trace!("修改限速");
+
+    let settings = settings::update(|s| {
+        s.bandwidth_limit = global;
+        s.transfer_bandwidth_limit = per_transfer;
+    })
+    .await?;
+    throttle::set_limits(settings.bandwidth_limit, settings.transfer_bandwidth_limit);
+
+    info!(message = "限速已修改", global = ?global, per_transfer = ?per_transfer);
+
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_settings() -> Settings {
+    trace!("获取配置");
+
+    SETTINGS.read().await.clone()
+}
+
+#[tauri::command]
+async fn update_settings(settings: Settings) -> FluxyResult<Settings> {
+    trace!("修改配置");
+
+    settings.validate()?;
+    let settings = settings::update(|s| *s = settings).await?;
+    apply_settings(&settings).await;
+    network::check_change().await;
+
+    info!(message = "配置已修改", settings = ?settings);
+
+    Ok(settings)
+}
+
+async fn file_metadata(path: &Path, relative_path: String) -> FluxyResult<SendFile> {
+    let filename = path.file_name().unwrap_or_default().to_string_lossy();
+    let extension = path.extension().unwrap_or_default().to_string_lossy();
+    let file = File::open(path).await.map_err(|e| {
+        error!(message = "打开文件失败", path = ?path, error = ?e);
+        e
+    })?;
+    let size = file
+        .metadata()
+        .await
+        .map_err(|e| {
+            error!(message = "获取文件元信息失败", path = ?path, error = ?e);
+            e
+        })?
+        .len();
+
+    Ok(SendFile::new(
+        filename,
+        path,
+        relative_path,
+        &extension,
+        size,
+    ))
+}
+
+/// 递归获取目录中所有文件的信息，相对路径以目录名开头，使用 `/` 分隔。
+///
+/// 不跟随指向目录的符号链接，避免出现循环。所选目录无法读取时返回错误，
+/// 其中无法读取的子目录和文件跳过并记录日志，不影响其他文件。
+async fn dir_files_metadata(root: &Path) -> FluxyResult<Vec<SendFile>> {
+    let mut files = Vec::new();
+
+    let root_name = root.file_name().unwrap_or_default().to_string_lossy();
+    let mut dirs = vec![(root.to_path_buf(), root_name.into_owned())];
+
+    while let Some((dir, prefix)) = dirs.pop() {
+        let mut entries = match fs::read_dir(&dir).await {
+            Ok(entries) => entries,
+            Err(e) if dir == root => {
+                error!(message = "读取目录失败", dir = ?dir, error = ?e);
+                return Err(e.into());
+            }
+            Err(e) => {
+                error!(message = "读取子目录失败，已跳过", dir = ?dir, error = ?e);
+                continue;
+            }
+        };
+
+        loop {
+            let entry = match entries.next_entry().await {
+                Ok(Some(entry)) => entry,
+                Ok(None) => break,
+                Err(e) => {
+                    error!(message = "读取目录项失败，已跳过剩余文件", dir = ?dir, error = ?e);
+                    break;
+                }
+            };
+
+            let path = entry.path();
+            let relative_path = format!("{}/{}", prefix, entry.file_name().to_string_lossy());
+
+            let file_type = match entry.file_type().await {
+                Ok(t) => t,
+                Err(e) => {
+                    error!(message = "读取文件类型失败，已跳过", path = ?path, error = ?e);
+                    continue;
+                }
+            };
+
+            if file_type.is_dir() {
+                dirs.push((path, relative_path));
+            } else if path.is_file() {
+                // 错误已在 file_metadata 中记录
+                if let Ok(file) = file_metadata(&path, relative_path).await {
+                    files.push(file);
+                }
+            }
+        }
+    }
+
+    files.sort_by(|a, b| a.relative_path().cmp(b.relative_path()));
+
+    Ok(files)
 }
 
 #[tauri::command]
@@ -167,25 +416,12 @@ async fn get_files_metadata(paths: Vec<PathBuf>) -> FluxyResult<Vec<SendFile>> {
 
     for path in paths.iter() {
         if path.is_dir() {
+            files.extend(dir_files_metadata(path).await?);
             continue;
         }
 
-        let filename = path.file_name().unwrap().to_str().unwrap();
-        let extension = path.extension().unwrap().to_str().unwrap();
-        let file = File::open(path).await.map_err(|e| {
-            error!(message = "打开文件失败", path = ?path, error = ?e);
-            e
-        })?;
-        let size = file
-            .metadata()
-            .await
-            .map_err(|e| {
-                error!(message = "获取文件元信息失败", path = ?path, error = ?e);
-                e
-            })?
-            .len();
-
-        files.push(SendFile::new(filename, path, extension, size))
+        let filename = path.file_name().unwrap_or_default().to_string_lossy();
+        files.push(file_metadata(path, filename.into_owned()).await?);
     }
 
     info!("所有待发送文件信息: {:?}", files);
@@ -193,9 +429,18 @@ async fn get_files_metadata(paths: Vec<PathBuf>) -> FluxyResult<Vec<SendFile>> {
     Ok(files)
 }
 
+/// 配置中指定的语言，未指定时使用系统语言。
+fn current_locale(settings: &Settings) -> Locale {
+    settings.locale.unwrap_or_else(|| {
+        let locale = get_locale().unwrap_or_else(|| String::from("en-US"));
+        debug!("current locale: {}", locale);
+        locale.into()
+    })
+}
+
 #[tauri::command]
-fn get_locale_translations() -> &'static Translations {
-    let locale: Locale = get_locale().unwrap_or_else(|| String::from("en-US")).into();
+async fn get_locale_translations() -> &'static Translations {
+    let locale = current_locale(&*SETTINGS.read().await);
     LOCALES[&locale]
 }
 
@@ -254,10 +499,12 @@ async fn main() -> FluxyResult<()> {
     #[cfg(not(debug_assertions))]
     builder.json().init();
 
-    let locale = get_locale().unwrap_or_else(|| String::from("en-US"));
-    debug!("current locale: {}", locale);
-    let locale: Locale = locale.into();
-    let translations = LOCALES[&locale];
+    let settings = settings::load().await;
+    apply_settings(&settings).await;
+    let port = settings.port;
+    let tls = settings.tls;
+    let translations = LOCALES[&current_locale(&settings)];
+    *SETTINGS.write().await = settings;
 
     #[cfg(target_os = "linux")]
     {
@@ -270,9 +517,12 @@ async fn main() -> FluxyResult<()> {
         }
     }
 
-    tokio::spawn(server::serve());
+    tokio::spawn(server::serve(port, tls));
     info!("已创建 serve 线程");
 
+    tokio::spawn(network::watch());
+    tokio::spawn(session::evict_expired());
+
     #[allow(unused_mut)]
     let mut builder = tauri::Builder::default()
         .setup(|app| {
@@ -288,8 +538,23 @@ async fn main() -> FluxyResult<()> {
         .invoke_handler(tauri::generate_handler![
             upload_qr_code,
             get_qr_code_state,
+            sessions,
+            cancel_transfer,
+            pause_transfer,
+            resume_transfer,
+            respond_upload_approval,
+            transfer_history,
+            export_history,
+            clear_history,
             downloads_dir,
             change_downloads_dir,
+            conflict_policy,
+            change_conflict_policy,
+            get_settings,
+            update_settings,
+            network_interfaces,
+            change_preferred_interface,
+            change_bandwidth_limit,
             get_files_metadata,
             get_send_files_url_qr_code,
             is_linux,
@@ -0,0 +1,312 @@
+use std::cmp::Reverse;
+use std::fmt::{self, Display, Formatter};
+use std::net::{IpAddr, Ipv6Addr};
+use std::time::Duration;
+
+use local_ip_address::list_afinet_netifas;
+use serde::Serialize;
+use tokio::sync::Mutex;
+
+use crate::server::MAIN_WINDOW;
+use crate::settings::SETTINGS;
+
+const NETWORK_CHANGED_EVENT: &str = "network://changed";
+
+/// 检查网络地址是否变化的间隔
+const WATCH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// 虚拟网卡、VPN 等手机通常无法访问的网卡名前缀，不区分大小写
+const VIRTUAL_PREFIXES: [&str; 18] = [
+    "docker",
+    "br-",
+    "veth",
+    "virbr",
+    "vmnet",
+    "vboxnet",
+    "utun",
+    "tun",
+    "tap",
+    "wg",
+    "zt",
+    "tailscale",
+    "awdl",
+    "llw",
+    "vethernet",
+    "vmware",
+    "virtualbox",
+    "hyper-v",
+];
+
+lazy_static! {
+    /// 上一次检查时的首选地址
+    static ref CURRENT_HOST: Mutex<Option<Host>> = Mutex::new(None);
+}
+
+/// 二维码 URL 中的主机地址
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Host {
+    ip: IpAddr,
+    /// IPv6 链路本地地址的区域，即其所在的网卡
+    zone: Option<String>,
+}
+
+impl Display for Host {
+    /// IPv6 地址放在方括号中，区域中的 `%` 按 RFC 6874 编码为 `%25`。
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match (&self.ip, &self.zone) {
+            (IpAddr::V4(ip), _) => write!(f, "{}", ip),
+            (IpAddr::V6(ip), None) => write!(f, "[{}]", ip),
+            (IpAddr::V6(ip), Some(zone)) => write!(f, "[{}%25{}]", ip, zone),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct NetworkInterface {
+    name: String,
+    addresses: Vec<IpAddr>,
+    /// 分数越高，手机越可能访问到此网卡的地址
+    score: i32,
+}
+
+impl NetworkInterface {
+    pub(crate) fn addresses(&self) -> &[IpAddr] {
+        &self.addresses
+    }
+
+    /// 分数最高的地址，IPv6 链路本地地址附带网卡名作为区域
+    fn best_address(&self) -> Option<Host> {
+        let ip = *self.addresses.iter().max_by_key(|ip| address_score(ip))?;
+
+        let zone = match ip {
+            IpAddr::V6(v6) if is_ipv6_link_local(&v6) => Some(self.name.clone()),
+            _ => None,
+        };
+
+        Some(Host { ip, zone })
+    }
+}
+
+fn is_ipv6_link_local(ip: &Ipv6Addr) -> bool {
+    (ip.segments()[0] & 0xffc0) == 0xfe80
+}
+
+/// 局域网常用的私有 IPv4 地址分数最高，其次是可路由的 IPv6 地址，链路本地地址分数最低。
+///
+/// IPv6 链路本地地址需要区域才能访问，而大多数手机浏览器不支持 URL 中的区域。
+fn address_score(ip: &IpAddr) -> i32 {
+    match ip {
+        IpAddr::V4(ip) => match ip.octets() {
+            [192, 168, ..] => 30,
+            [10, ..] => 20,
+            [172, b, ..] if (16..32).contains(&b) => 10,
+            _ if ip.is_link_local() => -50,
+            _ => 0,
+        },
+        IpAddr::V6(ip) if is_ipv6_link_local(ip) => -40,
+        IpAddr::V6(_) => -5,
+    }
+}
+
+fn is_virtual(name: &str) -> bool {
+    let name = name.to_lowercase();
+    VIRTUAL_PREFIXES.iter().any(|p| name.starts_with(p))
+}
+
+/// 列出所有网卡及其 IPv4/IPv6 地址，按分数从高到低排序，不包含回环地址。
+pub(crate) fn interfaces() -> Vec<NetworkInterface> {
+    let netifas = match list_afinet_netifas() {
+        Ok(n) => n,
+        Err(e) => {
+            error!(message = "获取网卡列表失败", error = ?e);
+            return Vec::new();
+        }
+    };
+
+    group_interfaces(netifas)
+}
+
+/// 按网卡名合并地址并计算分数，与系统接口分开以便测试。
+fn group_interfaces<I>(netifas: I) -> Vec<NetworkInterface>
+where
+    I: IntoIterator<Item = (String, IpAddr)>,
+{
+    let mut interfaces: Vec<NetworkInterface> = Vec::new();
+    for (name, ip) in netifas {
+        if ip.is_loopback() {
+            continue;
+        }
+
+        match interfaces.iter_mut().find(|i| i.name == name) {
+            Some(i) => i.addresses.push(ip),
+            None => interfaces.push(NetworkInterface {
+                name,
+                addresses: vec![ip],
+                score: 0,
+            }),
+        }
+    }
+
+    for interface in interfaces.iter_mut() {
+        let best = interface.addresses.iter().map(address_score).max();
+        interface.score =
+            best.unwrap_or(i32::MIN / 2) - if is_virtual(&interface.name) { 40 } else { 0 };
+    }
+
+    interfaces.sort_by_key(|i| Reverse(i.score));
+
+    interfaces
+}
+
+/// 二维码中使用的地址。
+///
+/// 优先使用 `preferred` 网卡的地址，该网卡不存在或没有可用地址时使用分数最高的网卡。
+pub(crate) fn local_host(preferred: Option<&str>) -> Option<Host> {
+    select_host(&interfaces(), preferred)
+}
+
+fn select_host(interfaces: &[NetworkInterface], preferred: Option<&str>) -> Option<Host> {
+    preferred
+        .and_then(|name| interfaces.iter().find(|i| i.name == name))
+        .and_then(NetworkInterface::best_address)
+        .or_else(|| interfaces.iter().find_map(NetworkInterface::best_address))
+}
+
+/// 按配置中的首选网卡获取当前地址。
+pub(crate) async fn current_host() -> Option<Host> {
+    let preferred = SETTINGS.read().await.preferred_interface.clone();
+    local_host(preferred.as_deref())
+}
+
+/// 地址发生变化时通知前端重新生成二维码。
+pub(crate) async fn check_change() {
+    let host = current_host().await;
+
+    let mut current = CURRENT_HOST.lock().await;
+    if *current == host {
+        return;
+    }
+
+    info!(message = "网络地址已变化", from = ?*current, to = ?host);
+
+    if let Some(w) = MAIN_WINDOW.get() {
+        let _ = w.emit(NETWORK_CHANGED_EVENT, host.as_ref().map(Host::to_string));
+    }
+
+    *current = host;
+}
+
+/// 定时检查网络地址是否变化。
+pub(crate) async fn watch() {
+    let mut interval = tokio::time::interval(WATCH_INTERVAL);
+
+    loop {
+        interval.tick().await;
+        check_change().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    fn netifas(list: &[(&str, &str)]) -> Vec<(String, IpAddr)> {
+        list.iter()
+            .map(|(name, addr)| (name.to_string(), ip(addr)))
+            .collect()
+    }
+
+    #[test]
+    fn rank_addresses() {
+        let ranked = [
+            "192.168.1.2",
+            "10.0.0.2",
+            "172.20.0.2",
+            "8.8.8.8",
+            "2001:db8::2",
+            "fe80::2",
+            "169.254.0.2",
+        ];
+
+        for pair in ranked.windows(2) {
+            assert!(
+                address_score(&ip(pair[0])) > address_score(&ip(pair[1])),
+                "{} 应优先于 {}",
+                pair[0],
+                pair[1]
+            );
+        }
+
+        // 不属于 172.16.0.0/12 的地址不是私有地址
+        assert_eq!(
+            address_score(&ip("172.32.0.2")),
+            address_score(&ip("8.8.8.8"))
+        );
+    }
+
+    #[test]
+    fn rank_interfaces() {
+        let interfaces = group_interfaces(netifas(&[
+            ("lo", "127.0.0.1"),
+            ("lo", "::1"),
+            ("docker0", "192.168.99.1"),
+            ("eth0", "fe80::1"),
+            ("eth0", "10.0.0.2"),
+            ("wlan0", "192.168.1.2"),
+            ("Tailscale", "100.64.0.2"),
+        ]));
+
+        let names: Vec<_> = interfaces.iter().map(|i| i.name.as_str()).collect();
+        // 回环地址被排除，虚拟网卡即使是私有地址也排在后面
+        assert_eq!(names, ["wlan0", "eth0", "docker0", "Tailscale"]);
+        assert_eq!(interfaces[1].addresses(), [ip("fe80::1"), ip("10.0.0.2")]);
+
+        let host = select_host(&interfaces, None).unwrap();
+        assert_eq!(host.to_string(), "192.168.1.2");
+    }
+
+    #[test]
+    fn select_preferred_interface() {
+        let interfaces = group_interfaces(netifas(&[
+            ("wlan0", "192.168.1.2"),
+            ("eth0", "10.0.0.2"),
+            ("eth1", "fe80::1"),
+        ]));
+
+        let host = select_host(&interfaces, Some("eth0")).unwrap();
+        assert_eq!(host.to_string(), "10.0.0.2");
+
+        // 链路本地地址附带网卡名作为区域
+        let host = select_host(&interfaces, Some("eth1")).unwrap();
+        assert_eq!(host.to_string(), "[fe80::1%25eth1]");
+
+        // 首选网卡不存在时使用分数最高的网卡
+        let host = select_host(&interfaces, Some("eth9")).unwrap();
+        assert_eq!(host.to_string(), "192.168.1.2");
+
+        assert_eq!(select_host(&[], Some("eth0")), None);
+    }
+
+    #[test]
+    fn display_host() {
+        let host = |addr: &str, zone: Option<&str>| Host {
+            ip: ip(addr),
+            zone: zone.map(str::to_string),
+        };
+
+        assert_eq!(host("192.168.1.2", None).to_string(), "192.168.1.2");
+        assert_eq!(host("2001:db8::1", None).to_string(), "[2001:db8::1]");
+        assert_eq!(
+            host("fe80::1", Some("eth0")).to_string(),
+            "[fe80::1%25eth0]"
+        );
+
+        // 与二维码 URL 的拼接方式一致，端口在方括号之外
+        let url = format!("http://{}:{}/", host("fe80::1", Some("eth0")), 8080);
+        assert_eq!(url, "http://[fe80::1%25eth0]:8080/");
+    }
+}
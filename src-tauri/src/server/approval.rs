@@ -0,0 +1,188 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use salvo::http::StatusCode;
+use salvo::Request;
+use serde::Serialize;
+use tokio::sync::watch;
+
+use super::error::{ServerError, ServerResult};
+use super::{format_file_size, session, MAIN_WINDOW};
+
+const APPROVAL_EVENT: &str = "upload://approval";
+/// 请求超时或已被处理，前端关闭对应的确认框
+const APPROVAL_CLOSED_EVENT: &str = "upload://approval-closed";
+
+/// 等待桌面端确认的时间，超时视为拒绝
+const APPROVAL_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// 接收文件前是否需要桌面端确认
+static REQUIRED: AtomicBool = AtomicBool::new(false);
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+lazy_static! {
+    /// 等待确认的上传，键为会话 id 和暂存文件路径，同一会话中同一文件的多个请求共用一次确认
+    static ref PENDING: Mutex<HashMap<(u64, PathBuf), Pending>> = Mutex::new(HashMap::new());
+}
+
+struct Pending {
+    id: u64,
+    /// 确认结果，`true` 为接收
+    decision: watch::Sender<Option<bool>>,
+}
+
+/// 发给前端的上传确认请求
+#[derive(Debug, Serialize, Clone)]
+struct ApprovalRequest<'a> {
+    id: u64,
+    session: u64,
+    name: &'a str,
+    /// 上传文件夹时文件所属的顶层文件夹
+    folder: Option<&'a str>,
+    size: &'a str,
+    client: Option<IpAddr>,
+    /// 发送端的 User-Agent
+    device: Option<String>,
+}
+
+/// 设置接收文件前是否需要桌面端确认。
+pub(crate) fn set_required(required: bool) {
+    REQUIRED.store(required, Ordering::Relaxed);
+}
+
+/// 等待桌面端确认接收文件。
+///
+/// 未开启确认、会话已被设为始终允许或此文件已确认过时直接通过。
+/// `key` 为暂存文件路径，同一文件的分块和续传只需确认一次。
+pub(super) async fn request(
+    req: &Request,
+    session: u64,
+    key: &Path,
+    name: &str,
+    folder: Option<&str>,
+    size: u64,
+) -> ServerResult<()> {
+    if !REQUIRED.load(Ordering::Relaxed) || session::is_approved(session, key).await {
+        return Ok(());
+    }
+
+    let key = (session, key.to_path_buf());
+
+    let mut decision = {
+        let mut pending = PENDING.lock().unwrap();
+
+        match pending.get(&key) {
+            Some(p) => p.decision.subscribe(),
+            None => {
+                let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+                let (tx, rx) = watch::channel(None);
+                pending.insert(key.clone(), Pending { id, decision: tx });
+
+                info!(
+                    message = "等待桌面端确认接收文件",
+                    id = id,
+                    name = name,
+                    size = size
+                );
+
+                if let Some(w) = MAIN_WINDOW.get() {
+                    let _ = w.emit(
+                        APPROVAL_EVENT,
+                        ApprovalRequest {
+                            id,
+                            session,
+                            name,
+                            folder,
+                            size: &format_file_size(size),
+                            client: session::client_ip(req),
+                            device: req.header::<String>("user-agent"),
+                        },
+                    );
+                }
+
+                tokio::spawn(expire(key, id));
+
+                rx
+            }
+        }
+    };
+
+    // 超时后发送端被丢弃，等待返回错误
+    let accepted = decision
+        .wait_for(Option::is_some)
+        .await
+        .ok()
+        .and_then(|d| *d);
+
+    match accepted {
+        Some(true) => Ok(()),
+        Some(false) => {
+            info!(message = "桌面端拒绝接收文件", name = name);
+            Err(ServerError::with_status(
+                StatusCode::FORBIDDEN,
+                "电脑端拒绝接收此文件",
+                None,
+            ))
+        }
+        None => {
+            info!(message = "等待桌面端确认超时", name = name);
+            Err(ServerError::with_status(
+                StatusCode::REQUEST_TIMEOUT,
+                "等待电脑端确认超时",
+                "请在电脑端确认后重新上传此文件",
+            ))
+        }
+    }
+}
+
+/// 超时后删除未处理的确认请求。
+async fn expire(key: (u64, PathBuf), id: u64) {
+    tokio::time::sleep(APPROVAL_TIMEOUT).await;
+
+    let mut pending = PENDING.lock().unwrap();
+    if pending.get(&key).is_some_and(|p| p.id == id) {
+        pending.remove(&key);
+        drop(pending);
+
+        if let Some(w) = MAIN_WINDOW.get() {
+            let _ = w.emit(APPROVAL_CLOSED_EVENT, id);
+        }
+    }
+}
+
+/// 处理确认请求，请求不存在或已超时时返回 `false`。
+///
+/// `always_allow` 为 `true` 时此会话之后的上传不再需要确认。
+pub(crate) async fn respond(id: u64, accept: bool, always_allow: bool) -> bool {
+    let ((session, key), pending) = {
+        let mut pending = PENDING.lock().unwrap();
+
+        let key = match pending.iter().find(|(_, p)| p.id == id) {
+            Some((k, _)) => k.clone(),
+            None => return false,
+        };
+        let p = pending.remove(&key).unwrap();
+
+        (key, p)
+    };
+
+    if accept {
+        session::approve(session, key, always_allow).await;
+    }
+
+    info!(
+        message = "已处理上传确认",
+        id = id,
+        accept = accept,
+        always_allow = always_allow
+    );
+
+    pending.decision.send_replace(Some(accept));
+
+    true
+}
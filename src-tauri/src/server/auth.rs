@@ -0,0 +1,65 @@
+use salvo::http::header::AUTHORIZATION;
+use salvo::http::{Request, Response, StatusCode};
+use salvo::{async_trait, Depot, FlowCtrl, Handler, Writer};
+
+use super::error::ServerError;
+use super::session::{self, Mode};
+
+/// 保存会话令牌的 cookie 名
+pub(super) const TOKEN_COOKIE: &str = "fluxy_token";
+
+fn request_token(req: &Request) -> Option<String> {
+    if let Some(cookie) = req.cookie(TOKEN_COOKIE) {
+        return Some(cookie.value().to_owned());
+    }
+
+    req.headers()
+        .get(AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(|s| s.trim().to_owned())
+}
+
+/// 拒绝未携带有效会话令牌的请求，认证通过时将会话 id 保存到 depot 中
+pub(super) struct Auth {
+    /// 允许访问的会话模式
+    mode: Mode,
+}
+
+impl Auth {
+    #[inline]
+    pub fn new(mode: Mode) -> Self {
+        Auth { mode }
+    }
+}
+
+#[async_trait]
+impl Handler for Auth {
+    async fn handle(
+        &self,
+        req: &mut Request,
+        depot: &mut Depot,
+        res: &mut Response,
+        ctrl: &mut FlowCtrl,
+    ) {
+        if let Some(token) = request_token(req) {
+            if let Some(id) = session::authenticate(req, &token, self.mode).await {
+                session::set_current(depot, id);
+                ctrl.call_next(req, depot, res).await;
+                session::release(id).await;
+                return;
+            }
+        }
+
+        warn!(message = "拒绝未授权的请求", path = ?req.uri().path(), ip = ?req.remote_addr());
+
+        ServerError::with_status(
+            StatusCode::UNAUTHORIZED,
+            "未授权的访问",
+            "请通过小路互传扫码访问",
+        )
+        .write(req, depot, res)
+        .await;
+        ctrl.skip_rest();
+    }
+}
@@ -10,6 +10,13 @@ pub(super) enum ServerError {
         error: String,
         advice: Option<String>,
     },
+    /// 需要返回 400 以外状态码的错误
+    Status {
+        #[serde(skip)]
+        code: StatusCode,
+        error: String,
+        advice: Option<String>,
+    },
     Internal,
 }
 
@@ -30,6 +37,20 @@ impl ServerError {
             },
         }
     }
+
+    pub(super) fn with_status<'a, O: Into<Option<&'a str>>>(
+        code: StatusCode,
+        error: &str,
+        advice: O,
+    ) -> Self {
+        let advice: Option<&str> = advice.into();
+
+        Self::Status {
+            code,
+            error: error.to_owned(),
+            advice: advice.map(|s| s.to_owned()),
+        }
+    }
 }
 
 #[async_trait]
@@ -40,6 +61,10 @@ impl Writer for ServerError {
                 res.status_code(StatusCode::BAD_REQUEST);
                 res.render(Json(&self));
             }
+            ServerError::Status { code, .. } => {
+                res.status_code(*code);
+                res.render(Json(&self));
+            }
             ServerError::Internal => {
                 res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
             }
@@ -0,0 +1,513 @@
+use std::collections::HashSet;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use salvo::http::StatusCode;
+use serde::{Deserialize, Serialize};
+use tokio::fs::{self, OpenOptions};
+use tokio::sync::Mutex;
+
+use super::error::{ServerError, ServerResult};
+use crate::lazy::APP_CONFIG_DIR;
+
+/// 文件名的最大字节数，大多数文件系统的限制
+const MAX_NAME_LEN: usize = 255;
+
+/// 未完成文件名中保留的原文件名的最大字节数，为后缀留出空间
+const MAX_PART_NAME_LEN: usize = 200;
+
+/// 未完成的上传文件的扩展名
+const PART_EXTENSION: &str = ".fluxy-part";
+
+/// 超过此时长未修改的未完成文件视为已放弃，启动时删除
+const STALE_PART_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// 记录写入过未完成文件的子文件夹的文件
+const PART_DIRS_FILE: &str = "part-dirs.json";
+
+lazy_static! {
+    /// 写入过未完成文件的子文件夹，第一次使用时从文件中读取
+    static ref PART_DIRS: Mutex<Option<HashSet<PathBuf>>> = Mutex::new(None);
+}
+
+/// 尝试添加 ` (n)` 后缀的最大次数
+const MAX_RENAME_ATTEMPTS: u32 = 10000;
+
+/// Windows 保留的设备名，不区分大小写，带扩展名时同样不可用
+const RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// 接收的文件与已有文件同名时的处理方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ConflictPolicy {
+    /// 在文件名后添加 ` (1)` 等后缀
+    #[default]
+    Rename,
+    /// 覆盖已有文件
+    Overwrite,
+    /// 拒绝接收
+    Reject,
+}
+
+/// 将客户端提供的文件名转换为可以安全保存的文件名。
+///
+/// 路径分隔符、控制字符和 Windows 不允许的字符替换为 `_`，Windows 保留的设备名前添加 `_`，
+/// 以未完成文件扩展名结尾的文件名将该扩展名的 `.` 替换为 `_`，避免被当作未完成文件删除。
+/// 无法得到有效文件名时返回 `None`。
+pub(super) fn sanitize_filename(name: &str) -> Option<String> {
+    let name: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | '<' | '>' | ':' | '"' | '|' | '?' | '*' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+
+    // Windows 会忽略文件名末尾的点和空格
+    let name = name.trim_start().trim_end_matches(['.', ' ']);
+
+    if name.is_empty() {
+        return None;
+    }
+
+    let stem = name.split('.').next().unwrap_or_default();
+    let mut name = if RESERVED_NAMES
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(stem.trim_end()))
+    {
+        format!("_{}", name)
+    } else {
+        name.to_owned()
+    };
+
+    truncate_filename(&mut name);
+
+    let suffix_start = name.len().saturating_sub(PART_EXTENSION.len());
+    if name.is_char_boundary(suffix_start)
+        && name[suffix_start..].eq_ignore_ascii_case(PART_EXTENSION)
+    {
+        name.replace_range(suffix_start..suffix_start + 1, "_");
+    }
+
+    Some(name)
+}
+
+/// 将客户端提供的相对路径转换为可以安全保存的各级路径名，最后一项为文件名。
+///
+/// `/` 与 `\\` 均视为分隔符，`.` 与空路径名被忽略，包含 `..` 的路径视为无效。
+pub(super) fn sanitize_relative_path(path: &str) -> Option<Vec<String>> {
+    let mut components = Vec::new();
+
+    for component in path.split(['/', '\\']) {
+        match component {
+            "" | "." => continue,
+            ".." => return None,
+            c => components.push(sanitize_filename(c)?),
+        }
+    }
+
+    if components.is_empty() {
+        return None;
+    }
+
+    Some(components)
+}
+
+/// 文件名过长时截断主干部分，尽量保留扩展名。
+fn truncate_filename(name: &mut String) {
+    if name.len() <= MAX_NAME_LEN {
+        return;
+    }
+
+    let extension = match name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() && ext.len() < 32 => format!(".{}", ext),
+        _ => String::new(),
+    };
+
+    let mut end = MAX_NAME_LEN - extension.len();
+    while !name.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    name.truncate(end);
+    name.push_str(&extension);
+}
+
+/// 上传中的文件先写入与保存路径同目录的未完成文件，接收完整并校验后再重命名，
+/// 避免其他程序读取到不完整的文件。
+///
+/// 文件名中包含会话 id，其他会话无法查询或续传此文件。
+pub(super) fn part_path(dir: &Path, name: &str, size: u64, session: u64, chunked: bool) -> PathBuf {
+    let mut end = name.len().min(MAX_PART_NAME_LEN);
+    while !name.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    let tag = if chunked { ".chunks" } else { "" };
+
+    dir.join(format!(
+        "{}.{}.{}{}{}",
+        &name[..end],
+        session,
+        size,
+        tag,
+        PART_EXTENSION
+    ))
+}
+
+fn part_dirs_path() -> PathBuf {
+    APP_CONFIG_DIR.join(PART_DIRS_FILE)
+}
+
+async fn load_part_dirs() -> HashSet<PathBuf> {
+    let path = part_dirs_path();
+
+    match fs::read(&path).await {
+        Ok(content) => serde_json::from_slice(&content).unwrap_or_else(|e| {
+            error!(message = "解析未完成文件目录列表失败", path = ?path, error = ?e);
+            HashSet::new()
+        }),
+        Err(e) => {
+            if e.kind() != ErrorKind::NotFound {
+                error!(message = "读取未完成文件目录列表失败", path = ?path, error = ?e);
+            }
+            HashSet::new()
+        }
+    }
+}
+
+async fn save_part_dirs(dirs: &HashSet<PathBuf>) {
+    let path = part_dirs_path();
+
+    let result = match serde_json::to_vec(dirs) {
+        Ok(content) => fs::write(&path, content).await,
+        Err(e) => Err(e.into()),
+    };
+    if let Err(e) = result {
+        error!(message = "保存未完成文件目录列表失败", path = ?path, error = ?e);
+    }
+}
+
+/// 记录上传文件夹时写入未完成文件的子文件夹，启动时清理其中过期的未完成文件。
+pub(super) async fn track_part_dir(dir: &Path) {
+    let mut part_dirs = PART_DIRS.lock().await;
+    if part_dirs.is_none() {
+        *part_dirs = Some(load_part_dirs().await);
+    }
+
+    let dirs = part_dirs.as_mut().unwrap();
+    if dirs.insert(dir.to_path_buf()) {
+        save_part_dirs(dirs).await;
+    }
+}
+
+/// 删除下载目录和记录的子文件夹中长时间未修改的未完成文件。
+///
+/// 只扫描这些文件夹本身，不遍历整个下载目录。没有未完成文件的子文件夹不再记录。
+pub(super) async fn remove_stale_parts(downloads_dir: &Path) {
+    let mut part_dirs = PART_DIRS.lock().await;
+    if part_dirs.is_none() {
+        *part_dirs = Some(load_part_dirs().await);
+    }
+
+    let dirs = part_dirs.as_mut().unwrap();
+    let before = dirs.len();
+
+    remove_stale_parts_in(downloads_dir).await;
+
+    let mut remaining = HashSet::with_capacity(dirs.len());
+    for dir in dirs.iter() {
+        if remove_stale_parts_in(dir).await {
+            remaining.insert(dir.clone());
+        }
+    }
+
+    if remaining.len() != before {
+        *dirs = remaining;
+        save_part_dirs(dirs).await;
+    }
+}
+
+/// 删除 `dir` 中过期的未完成文件，返回是否还有未过期的未完成文件。
+async fn remove_stale_parts_in(dir: &Path) -> bool {
+    let mut entries = match fs::read_dir(dir).await {
+        Ok(e) => e,
+        Err(e) => {
+            if e.kind() != ErrorKind::NotFound {
+                error!(message = "读取接收目录失败", dir = ?dir, error = ?e);
+            }
+            return false;
+        }
+    };
+
+    let mut remaining = false;
+
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let path = entry.path();
+        if !path.to_string_lossy().ends_with(PART_EXTENSION) {
+            continue;
+        }
+
+        let stale = entry
+            .metadata()
+            .await
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|t| SystemTime::now().duration_since(t).ok())
+            .is_some_and(|age| age > STALE_PART_AGE);
+        if !stale {
+            remaining = true;
+            continue;
+        }
+
+        match fs::remove_file(&path).await {
+            Ok(_) => info!(message = "已删除过期的未完成文件", path = ?path),
+            Err(e) => error!(message = "删除过期的未完成文件失败", path = ?path, error = ?e),
+        }
+    }
+
+    remaining
+}
+
+/// 第 `n` 个候选文件名，`n` 为 0 时即原文件名。
+fn candidate(dir: &Path, name: &str, n: u32) -> PathBuf {
+    if n == 0 {
+        return dir.join(name);
+    }
+
+    let path = Path::new(name);
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or(name);
+
+    match path.extension().and_then(|s| s.to_str()) {
+        Some(ext) => dir.join(format!("{} ({}).{}", stem, n, ext)),
+        None => dir.join(format!("{} ({})", stem, n)),
+    }
+}
+
+fn conflict_error(path: &Path) -> ServerError {
+    error!(message = "文件已存在，拒绝接收", path = ?path);
+
+    ServerError::with_status(StatusCode::CONFLICT, "文件已存在", "请修改文件名后重新上传")
+}
+
+/// 按冲突策略预测文件的保存路径，不占用该路径。
+///
+/// 策略为拒绝且文件已存在时返回错误，以便在接收前就拒绝上传。
+pub(super) fn preview_destination(
+    dir: &Path,
+    name: &str,
+    policy: ConflictPolicy,
+) -> ServerResult<PathBuf> {
+    let path = dir.join(name);
+
+    match policy {
+        ConflictPolicy::Overwrite => Ok(path),
+        ConflictPolicy::Reject if path.exists() => Err(conflict_error(&path)),
+        ConflictPolicy::Reject => Ok(path),
+        ConflictPolicy::Rename => Ok((0..MAX_RENAME_ATTEMPTS)
+            .map(|n| candidate(dir, name, n))
+            .find(|p| !p.exists())
+            .unwrap_or(path)),
+    }
+}
+
+/// 按冲突策略将接收完整的暂存文件移动到 `dir` 中，返回最终的保存路径。
+///
+/// 除覆盖外，先以新建空文件的方式独占目标路径，再用暂存文件替换它，
+/// 避免检查与移动之间出现同名文件时被覆盖。
+pub(super) async fn persist(
+    staging: &Path,
+    dir: &Path,
+    name: &str,
+    policy: ConflictPolicy,
+) -> ServerResult<PathBuf> {
+    let path = match policy {
+        ConflictPolicy::Overwrite => dir.join(name),
+        ConflictPolicy::Reject => {
+            let path = dir.join(name);
+            reserve(&path).await?.ok_or_else(|| conflict_error(&path))?
+        }
+        ConflictPolicy::Rename => {
+            let mut reserved = None;
+            for n in 0..MAX_RENAME_ATTEMPTS {
+                reserved = reserve(&candidate(dir, name, n)).await?;
+                if reserved.is_some() {
+                    break;
+                }
+            }
+
+            match reserved {
+                Some(p) => p,
+                None => {
+                    error!(message = "找不到可用的文件名", name = name);
+                    return Err(ServerError::Internal);
+                }
+            }
+        }
+    };
+
+    if let Err(e) = fs::rename(staging, &path).await {
+        error!(message = "移动暂存文件时出错", from = ?staging, to = ?path, error = ?e);
+
+        if policy != ConflictPolicy::Overwrite {
+            let _ = fs::remove_file(&path).await;
+        }
+
+        return Err(ServerError::Internal);
+    }
+
+    Ok(path)
+}
+
+/// 新建空文件占用路径，路径已存在时返回 `None`。
+async fn reserve(path: &Path) -> ServerResult<Option<PathBuf>> {
+    match OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)
+        .await
+    {
+        Ok(_) => Ok(Some(path.to_path_buf())),
+        Err(e) if e.kind() == ErrorKind::AlreadyExists => Ok(None),
+        Err(e) => {
+            error!(message = "占用保存路径时出错", path = ?path, error = ?e);
+            Err(ServerError::Internal)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sanitize_invalid_characters() {
+        assert_eq!(
+            sanitize_filename("a/b\\c:d*e?.txt").as_deref(),
+            Some("a_b_c_d_e_.txt")
+        );
+        assert_eq!(sanitize_filename("a\nb\t.txt").as_deref(), Some("a_b_.txt"));
+        assert_eq!(sanitize_filename("  name. . ").as_deref(), Some("name"));
+    }
+
+    #[test]
+    fn reject_empty_filename() {
+        assert_eq!(sanitize_filename(""), None);
+        assert_eq!(sanitize_filename("   "), None);
+        assert_eq!(sanitize_filename("."), None);
+        assert_eq!(sanitize_filename(".."), None);
+    }
+
+    #[test]
+    fn prefix_reserved_names() {
+        assert_eq!(sanitize_filename("CON").as_deref(), Some("_CON"));
+        assert_eq!(sanitize_filename("nul.txt").as_deref(), Some("_nul.txt"));
+        assert_eq!(
+            sanitize_filename("Com1 .tar.gz").as_deref(),
+            Some("_Com1 .tar.gz")
+        );
+        assert_eq!(
+            sanitize_filename("CONSOLE.txt").as_deref(),
+            Some("CONSOLE.txt")
+        );
+    }
+
+    #[test]
+    fn rename_part_extension() {
+        assert_eq!(
+            sanitize_filename("a.fluxy-part").as_deref(),
+            Some("a_fluxy-part")
+        );
+        assert_eq!(
+            sanitize_filename("a.FLUXY-PART").as_deref(),
+            Some("a_FLUXY-PART")
+        );
+        assert_eq!(
+            sanitize_filename(".fluxy-part").as_deref(),
+            Some("_fluxy-part")
+        );
+        assert_eq!(
+            sanitize_filename("a.fluxy-part.txt").as_deref(),
+            Some("a.fluxy-part.txt")
+        );
+    }
+
+    #[test]
+    fn truncate_long_filename() {
+        let name = sanitize_filename(&format!("{}.txt", "a".repeat(300))).unwrap();
+        assert_eq!(name.len(), MAX_NAME_LEN);
+        assert!(name.ends_with(".txt"));
+
+        // 不在多字节字符中间截断
+        let name = sanitize_filename(&format!("{}.txt", "文".repeat(100))).unwrap();
+        assert!(name.len() <= MAX_NAME_LEN);
+        assert!(name.ends_with("文.txt"));
+
+        // 过长的扩展名不保留
+        let name = sanitize_filename(&format!("a.{}", "b".repeat(300))).unwrap();
+        assert_eq!(name.len(), MAX_NAME_LEN);
+    }
+
+    #[test]
+    fn sanitize_relative_components() {
+        assert_eq!(
+            sanitize_relative_path("dir/sub\\file.txt"),
+            Some(vec![
+                "dir".to_owned(),
+                "sub".to_owned(),
+                "file.txt".to_owned()
+            ])
+        );
+        assert_eq!(
+            sanitize_relative_path("./dir//file.txt"),
+            Some(vec!["dir".to_owned(), "file.txt".to_owned()])
+        );
+    }
+
+    #[test]
+    fn reject_parent_components() {
+        assert_eq!(sanitize_relative_path(".."), None);
+        assert_eq!(sanitize_relative_path("../file.txt"), None);
+        assert_eq!(sanitize_relative_path("dir/../../file.txt"), None);
+        assert_eq!(sanitize_relative_path("dir\\..\\file.txt"), None);
+        assert_eq!(sanitize_relative_path(""), None);
+        assert_eq!(sanitize_relative_path("/"), None);
+    }
+
+    #[test]
+    fn keep_absolute_paths_relative() {
+        assert_eq!(
+            sanitize_relative_path("/etc/passwd"),
+            Some(vec!["etc".to_owned(), "passwd".to_owned()])
+        );
+        assert_eq!(
+            sanitize_relative_path("C:\\Windows\\win.ini"),
+            Some(vec![
+                "C_".to_owned(),
+                "Windows".to_owned(),
+                "win.ini".to_owned()
+            ])
+        );
+        assert_eq!(
+            sanitize_relative_path("\\\\server\\share\\a"),
+            Some(vec![
+                "server".to_owned(),
+                "share".to_owned(),
+                "a".to_owned()
+            ])
+        );
+    }
+
+    #[test]
+    fn sanitize_reserved_path_components() {
+        assert_eq!(
+            sanitize_relative_path("aux/com1.txt"),
+            Some(vec!["_aux".to_owned(), "_com1.txt".to_owned()])
+        );
+    }
+}
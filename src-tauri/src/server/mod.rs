@@ -1,54 +1,104 @@
+pub(super) mod approval;
+mod auth;
 mod error;
+mod filename;
 mod logger;
-
-use std::collections::HashMap;
+pub(super) mod quota;
+pub(super) mod session;
+mod tls;
+pub(super) mod transfer;
+mod upload;
+mod zip;
+
+use std::io;
+use std::iter;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::path::{Path, PathBuf};
-use std::process;
+use std::sync::atomic::{AtomicU16, Ordering};
 use std::sync::OnceLock;
-use std::time::Instant;
 
 use rust_embed::RustEmbed;
+use salvo::conn::{Listener, TcpAcceptor};
 use salvo::fs::NamedFile;
+use salvo::http::cookie::{Cookie, SameSite};
+use salvo::http::header::{
+    HeaderValue, CONTENT_DISPOSITION, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE,
+};
 use salvo::prelude::*;
 #[cfg(not(debug_assertions))]
 use salvo::serve_static::static_embed;
 use serde::{Deserialize, Serialize};
+use socket2::{Domain, Protocol, Socket, Type};
 use tauri::Window;
 use tokio::fs;
-use tokio::fs::File;
+use tokio::net::TcpListener as TokioTcpListener;
 use tokio::sync::RwLock;
-use tokio_util::io::StreamReader;
+use tokio_util::io::ReaderStream;
 
 use crate::error::FluxyResult;
+use crate::history::{Direction, Outcome, Record};
 #[cfg(debug_assertions)]
-use crate::lazy::LOCAL_IP;
+use crate::network;
+use crate::server::auth::{Auth, TOKEN_COOKIE};
 use crate::server::logger::Logger;
-use crate::stream::ReadProgressStream;
+use crate::server::tls::BoundListener;
+use crate::server::zip::ZipArchive;
+use crate::stream::{mb_per_sec, Progress, ReadProgressStream};
+use crate::throttle::ThrottledStream;
 
 use self::error::{ServerError, ServerResult};
 
+pub(super) use self::filename::ConflictPolicy;
+pub(super) use self::session::{Mode, SessionInfo};
+pub(super) use self::tls::fingerprint;
+
 const UPLOAD_EVENT: &str = "upload://progress";
+const DOWNLOAD_EVENT: &str = "download://progress";
+
+/// 默认的监听端口
+pub(super) const DEFAULT_PORT: u16 = 5800;
+/// 首选端口被占用时，依次尝试其后的端口数，都被占用时由系统分配
+const PORT_SCAN_RANGE: u16 = 20;
+/// 实际监听的端口，服务启动前为 0
+static PORT: AtomicU16 = AtomicU16::new(0);
+
 pub static MAIN_WINDOW: OnceLock<Window> = OnceLock::new();
 
 lazy_static! {
     pub(super) static ref DOWNLOADS_DIR: RwLock<PathBuf> =
         RwLock::new(dirs::download_dir().unwrap().join("alley"));
-    pub(super) static ref QR_CODE_MAP: RwLock<HashMap<u64, bool>> = RwLock::new(HashMap::new());
-    pub(super) static ref SEND_FILES: RwLock<Option<Vec<SendFile>>> = RwLock::new(None);
+    pub(super) static ref CONFLICT_POLICY: RwLock<ConflictPolicy> =
+        RwLock::new(ConflictPolicy::default());
 }
 
 #[derive(Debug, Serialize, Clone)]
 struct Task<'a> {
+    /// 传输 id，桌面端据此取消或暂停传输
+    id: u64,
     path: &'a Path,
     name: &'a str,
     percent: f64,
-    speed: f64, // MB/s
+    /// 最近一段时间内的速度，MB/s
+    speed: f64,
+    /// 从开始传输到现在的平均速度，MB/s
+    average_speed: f64,
+    /// 已传输的字节数
+    transferred: u64,
+    /// 文件的总字节数
+    total: u64,
+    /// 预计剩余时间（秒），速度未知时为空
+    eta: Option<f64>,
     size: &'a str,
     aborted: bool,
+    /// 校验通过的文件 SHA-256
+    hash: Option<&'a str>,
+    /// 上传文件夹时文件所属的顶层文件夹
+    folder: Option<&'a str>,
 }
 
 impl<'a> Task<'a> {
     fn new(
+        id: u64,
         path: &'a Path,
         name: &'a str,
         size: &'a str,
@@ -57,14 +107,48 @@ impl<'a> Task<'a> {
         aborted: bool,
     ) -> Self {
         Self {
+            id,
             path,
             name,
             percent,
             speed,
+            average_speed: 0.,
+            transferred: 0,
+            total: 0,
+            eta: None,
             size,
             aborted,
+            hash: None,
+            folder: None,
         }
     }
+
+    /// 设置已传输与总字节数，并按当前速度估算剩余时间。
+    fn with_progress(mut self, transferred: u64, total: u64, average_speed: f64) -> Self {
+        let remaining = total.saturating_sub(transferred);
+
+        self.transferred = transferred;
+        self.total = total;
+        self.average_speed = average_speed;
+        self.eta = if remaining == 0 {
+            Some(0.)
+        } else if self.speed > 0. {
+            Some(remaining as f64 / (1024 * 1024) as f64 / self.speed)
+        } else {
+            None
+        };
+        self
+    }
+
+    fn with_hash(mut self, hash: &'a str) -> Self {
+        self.hash = Some(hash);
+        self
+    }
+
+    fn in_folder(mut self, folder: Option<&'a str>) -> Self {
+        self.folder = folder;
+        self
+    }
 }
 
 #[cfg(debug_assertions)]
@@ -80,9 +164,17 @@ async fn index(req: &Request, res: &mut Response) -> ServerResult<()> {
         Some(s) => s,
     };
 
+    let host = match network::current_host().await {
+        Some(h) => h,
+        None => {
+            error!("未找到可用的网络地址");
+            return Err(ServerError::Internal);
+        }
+    };
+
     res.render(Redirect::found(format!(
         "http://{}:5173?mode={}",
-        *LOCAL_IP, mode
+        host, mode
     )));
 
     Ok(())
@@ -100,29 +192,36 @@ async fn connect(req: &Request, res: &mut Response) -> ServerResult<()> {
         }
     };
 
-    let mode = match req.query::<String>("mode") {
+    let token = match req.query::<String>("token") {
         Some(s) => s,
         None => {
-            error!("请求 url 中未找到 mode");
-            return Err(ServerError::new("缺少 mode", "请通过小路互传扫码访问"));
+            error!("请求 url 中未找到 token");
+            return Err(ServerError::new("缺少 token", "请通过小路互传扫码访问"));
         }
     };
 
-    let mut qr_code_map = QR_CODE_MAP.write().await;
-    if qr_code_map.contains_key(&id) {
-        error!(message = "此二维码已被使用", id = id);
-        return Err(ServerError::new(
-            "此二维码已被使用",
-            "请刷新小路互传页面生成新的二维码",
-        ));
-    }
+    let mode = session::connect(req, id, &token).await?;
 
-    qr_code_map.insert(id, true);
+    res.add_cookie(
+        Cookie::build((TOKEN_COOKIE, token))
+            .path("/")
+            .http_only(true)
+            .same_site(SameSite::Lax)
+            .secure(tls::fingerprint().is_some())
+            .build(),
+    );
 
     info!(message = "客户端连接成功", ip = ?req.remote_addr());
 
-    // 客户端重定向到首页
-    res.render(Redirect::found("/?mode=".to_owned() + &mode));
+    // 客户端重定向到首页，并带上证书指纹供页面展示，只转发十六进制和 `:` 组成的指纹
+    let mut location = "/?mode=".to_owned() + mode.as_str();
+    if let Some(fp) = req
+        .query::<String>("fp")
+        .filter(|fp| fp.chars().all(|c| c.is_ascii_hexdigit() || c == ':'))
+    {
+        location = location + "&fp=" + &fp;
+    }
+    res.render(Redirect::found(location));
 
     Ok(())
 }
@@ -143,41 +242,97 @@ fn format_file_size(size: u64) -> String {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub(super) struct SendFile {
+    /// 下载时使用的不透明 id，注册到发送会话时生成
+    #[serde(default)]
+    id: String,
     name: String,
     path: PathBuf,
+    /// 相对于所选文件或目录的路径，使用 `/` 分隔，客户端据此还原目录结构
+    #[serde(default)]
+    relative_path: String,
     extension: String,
     size: String,
 }
 
 impl SendFile {
-    pub(super) fn new<S: Into<String>, P: Into<PathBuf>>(
+    pub(super) fn new<S: Into<String>, P: Into<PathBuf>, R: Into<String>>(
         name: S,
         path: P,
+        relative_path: R,
         extension: &str,
         size: u64,
     ) -> Self {
         Self {
+            id: String::new(),
             name: name.into(),
             path: path.into(),
+            relative_path: relative_path.into(),
             extension: extension.to_uppercase(),
             size: format_file_size(size),
         }
     }
+
+    pub(super) fn relative_path(&self) -> &str {
+        &self.relative_path
+    }
+}
+
+/// 返回给手机端的文件信息，不包含文件在电脑上的路径
+#[derive(Debug, Serialize)]
+struct PhoneFile<'a> {
+    id: &'a str,
+    name: &'a str,
+    relative_path: &'a str,
+    extension: &'a str,
+    size: &'a str,
+}
+
+impl<'a> From<&'a SendFile> for PhoneFile<'a> {
+    fn from(file: &'a SendFile) -> Self {
+        Self {
+            id: &file.id,
+            name: &file.name,
+            relative_path: &file.relative_path,
+            extension: &file.extension,
+            size: &file.size,
+        }
+    }
+}
+
+/// 已注册的发送文件的路径
+#[derive(Debug, Clone)]
+pub(super) struct SendFilePath {
+    path: PathBuf,
+    /// 打包下载时文件在压缩包中的路径
+    relative_path: String,
 }
 
 #[handler]
-async fn download_file(req: &Request, res: &mut Response) -> ServerResult<()> {
-    let path = match req.param::<PathBuf>("path") {
+async fn download_file(req: &Request, depot: &Depot, res: &mut Response) -> ServerResult<()> {
+    let session = session::current(depot)?;
+
+    let id = match req.param::<String>("id") {
         None => {
-            error!("请求 url 中未找到 path");
-            return Err(ServerError::new("缺少 path", "请通过小路互传扫码访问"));
+            error!("请求 url 中未找到 id");
+            return Err(ServerError::new("缺少 id", "请通过小路互传扫码访问"));
+        }
+        Some(id) => id,
+    };
+
+    let path = match session::file_path(session, &id).await {
+        None => {
+            error!(message = "请求的文件不在发送列表中", id = id, ip = ?req.remote_addr());
+            return Err(ServerError::new(
+                "文件不在发送列表中",
+                "请在小路互传中重新选择要发送的文件",
+            ));
         }
         Some(p) => p,
     };
 
-    debug!(message = "下载文件", path = ?path);
+    debug!(message = "下载文件", session = session, id = id, path = ?path);
 
     if !path.exists() {
         error!(message = "path 不存在", path = ?path);
@@ -186,159 +341,314 @@ async fn download_file(req: &Request, res: &mut Response) -> ServerResult<()> {
 
     let builder = NamedFile::builder(&path);
 
-    let filename = path.file_name().unwrap().to_str().unwrap();
+    // 文件名可能不是有效的 UTF-8，不能 unwrap
+    let filename = path
+        .file_name()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let size = fs::metadata(&path).await.map(|m| m.len()).ok();
+    let record = Record::new(
+        Direction::Download,
+        filename.as_str(),
+        size.unwrap_or(0),
+        session::client_ip(req),
+    );
 
     builder
         .attached_name(filename)
         .send(req.headers(), res)
         .await;
 
+    // 只跟踪发送了文件内容的响应，不包括 304、416 等
+    if let (Some(size), StatusCode::OK | StatusCode::PARTIAL_CONTENT) =
+        (size, res.status_code.unwrap_or(StatusCode::OK))
+    {
+        let transfer_id = session::download_id(session, &id).await;
+        session::hold(session).await;
+        track_download(
+            res,
+            session,
+            DownloadInfo::new(transfer_id, &path, size),
+            record,
+        );
+    }
+
     Ok(())
 }
 
-#[handler]
-async fn files(res: &mut Response) -> ServerResult<()> {
-    let send_files_guard = SEND_FILES.read().await;
+/// 前端显示的下载文件信息
+#[derive(Clone)]
+struct DownloadInfo {
+    id: u64,
+    path: PathBuf,
+    name: String,
+    size: u64,
+    formatted_size: String,
+}
 
-    let send_files = match send_files_guard.as_ref() {
-        None => {
-            error!("未选择文件");
-            return Err(ServerError::new(
-                "小路互传客户端未选择文件",
-                "请先在小路互传选择一些文件",
-            ));
+impl DownloadInfo {
+    fn new(id: u64, path: &Path, size: u64) -> Self {
+        Self {
+            id,
+            path: path.to_path_buf(),
+            name: path
+                .file_name()
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            size,
+            formatted_size: format_file_size(size),
         }
-        Some(arr) => arr,
-    };
+    }
 
-    debug!(message = "发送的文件列表", files = ?send_files);
+    /// 已发送到 `position` 处时的百分比
+    fn percent(&self, position: u64) -> f64 {
+        if self.size == 0 {
+            100.
+        } else {
+            (position.min(self.size) * 1000 / self.size) as f64 / 10.0
+        }
+    }
 
-    res.render(Json(send_files));
+    /// 通知前端已发送到文件的 `position` 处。
+    fn emit(&self, position: u64, speed: f64, average_speed: f64, aborted: bool) {
+        if let Some(w) = MAIN_WINDOW.get() {
+            let task = Task::new(
+                self.id,
+                &self.path,
+                &self.name,
+                &self.formatted_size,
+                self.percent(position),
+                speed,
+                aborted,
+            )
+            .with_progress(position, self.size, average_speed);
+            let _ = w.emit(DOWNLOAD_EVENT, task);
+        }
+    }
+}
 
-    drop(send_files_guard);
+/// 为响应中的文件体报告发送进度，发送结束后记录传输结果并释放 [`session::hold`] 保持的会话。
+///
+/// 范围请求从 `Content-Range` 的起始位置开始计算进度，发送完请求的范围即视为完成。
+/// 播放器拖动进度、分段下载等会对同一文件发起多个范围请求，
+/// 只有请求范围到达文件末尾时才写入历史记录，每个文件只记录一次。
+fn track_download(res: &mut Response, session: u64, info: DownloadInfo, record: Record) {
+    let (first, len) = res
+        .headers()
+        .get(CONTENT_RANGE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(upload::parse_content_range)
+        .map(|(first, last, _)| (first, last - first + 1))
+        .unwrap_or((0, info.size));
+    let reaches_end = first + len == info.size;
+
+    let body = res.take_body();
+    let stream = ReadProgressStream::new(
+        body,
+        Box::new({
+            let info = info.clone();
+            move |progress: Progress| {
+                info.emit(
+                    first + progress.bytes,
+                    progress.speed,
+                    progress.average_speed,
+                    false,
+                );
+            }
+        }),
+    )
+    .with_len(len)
+    .on_finish(Box::new(move |cost, sent| {
+        let completed = sent >= len;
+
+        let speed = mb_per_sec(sent, cost);
+        info.emit(first + sent, speed, speed, !completed);
+
+        if completed {
+            info!(message = "文件已发送", path = ?info.path, first = first, len = len, cost = ?cost);
+        } else {
+            info!(message = "文件发送中断", path = ?info.path, sent = sent, len = len);
+        }
 
-    info!("文件列表已发送到客户端，清空文件列表");
-    let mut send_files = SEND_FILES.write().await;
-    *send_files = None;
+        tokio::spawn(async move {
+            let outcome = if completed {
+                // 发送到文件末尾才算完成一个文件
+                session::record_transfer(session, u64::from(reaches_end), sent).await;
+                Outcome::Completed
+            } else {
+                Outcome::Interrupted
+            };
+
+            if reaches_end {
+                record.finish(outcome, Some(&info.path), None, sent).await;
+            }
+            session::release(session).await;
+        });
+    }));
 
-    Ok(())
+    res.stream(stream);
 }
 
+/// 将当前发送会话中的所有文件打包为一个 ZIP 下载。
+///
+/// 压缩包边读取文件边发送，不会生成临时文件。
 #[handler]
-async fn upload(req: &mut Request) -> ServerResult<()> {
-    debug!(message = "收到上传任务", ip = ?req.remote_addr());
+async fn download_all(req: &Request, depot: &Depot, res: &mut Response) -> ServerResult<()> {
+    let session = session::current(depot)?;
 
-    let name = match req.query::<String>("name") {
-        Some(s) => s,
-        None => {
-            error!("请求地址中未找到文件名");
-            return Err(ServerError::new("文件名为空", "请通过小路互传扫码访问"));
-        }
-    };
-    debug!(message = "接收的文件名", name = name);
+    let mut files = session::file_paths(session).await;
+    let count = files.len() as u64;
+    if files.is_empty() {
+        error!("未选择文件");
+        return Err(ServerError::new(
+            "小路互传客户端未选择文件",
+            "请先在小路互传选择一些文件",
+        ));
+    }
 
-    let size: u64 = match req.header("content-length") {
-        Some(n) => n,
-        None => {
-            error!("请求头中未找到文件大小");
+    files.sort_by(|a, b| a.relative_path.cmp(&b.relative_path));
 
-            return Err(ServerError::new("文件长度为空", "请通过小路互传扫码访问"));
-        }
-    };
+    let archive = ZipArchive::new(files.into_iter().map(|f| (f.path, f.relative_path)))
+        .await
+        .map_err(|e| {
+            error!(message = "创建压缩包失败", error = ?e);
+            ServerError::new("文件不存在", "路径错误或该文件已被删除")
+        })?;
 
-    info!(
-        message= "收到有效的上传任务",
-        name = name,
-        size = size,
-        ip = ?req.remote_addr()
+    info!(message = "打包下载所有文件", size = archive.len(), ip = ?req.remote_addr());
+
+    let headers = res.headers_mut();
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/zip"));
+    headers.insert(
+        CONTENT_DISPOSITION,
+        HeaderValue::from_static(r#"attachment; filename="fluxy.zip""#),
     );
+    headers.insert(CONTENT_LENGTH, archive.len().into());
 
-    let start = Instant::now();
+    let record = Record::new(
+        Direction::Download,
+        "fluxy.zip",
+        archive.len(),
+        session::client_ip(req),
+    );
 
-    let formatted_size = format_file_size(size);
-    let path: PathBuf = DOWNLOADS_DIR.read().await.as_path().join(&name);
+    let (reader, mut writer) = tokio::io::duplex(64 * 1024);
 
-    let body = req.take_body();
-    let stream = ReadProgressStream::new(
-        body,
-        Box::new({
-            let name = name.clone();
-            let formatted_size = formatted_size.clone();
-            let path = path.clone();
-            move |cost, progress| {
-                let percent = (progress * 1000 / size) as f64 / 10.0;
-
-                // 纳秒转为浮点数的秒
-                let cost_senconds = cost.as_nanos() as f64 / 1000000000.0;
-
-                // chunk_size 转为浮点数的 mb
-                let progress = progress as f64 / (1024 * 1024) as f64;
-
-                let speed = progress / cost_senconds;
-
-                if let Some(w) = MAIN_WINDOW.get() {
-                    let _ = w.emit(
-                        UPLOAD_EVENT,
-                        Task::new(&path, &name, &formatted_size, percent, speed, false),
-                    );
-                }
+    session::hold(session).await;
+
+    tokio::spawn(async move {
+        // 出错时直接断开，客户端收到的字节数少于 Content-Length，会认为下载失败
+        match archive.write_to(&mut writer).await {
+            Ok(()) => {
+                // 全部写入后才计入会话的传输统计
+                session::record_transfer(session, count, archive.len()).await;
+                record
+                    .finish(Outcome::Completed, None, None, archive.len())
+                    .await
             }
-        }),
-    );
+            Err(e) => {
+                error!(message = "发送压缩包时出错", error = ?e);
+                record.finish(Outcome::Interrupted, None, None, 0).await;
+            }
+        }
 
-    let file_path = DOWNLOADS_DIR.read().await.join(&name);
+        session::release(session).await;
+    });
 
-    debug!(message = "保存的文件路径", path = ?file_path);
+    res.stream(ThrottledStream::new(ReaderStream::new(reader)));
 
-    let mut stream_reader = StreamReader::new(stream);
-    let mut file = File::create(&file_path).await.map_err(|e| {
-        error!(message = "新建文件时出错", path = ?file_path, error = ?e);
-        ServerError::Internal
-    })?;
-    if let Err(e) = tokio::io::copy(&mut stream_reader, &mut file).await {
-        error!(message = "复制文件流时出错", path = ?file_path, error = ?e);
+    Ok(())
+}
 
-        if let Some(w) = MAIN_WINDOW.get() {
-            let _ = w.emit(
-                UPLOAD_EVENT,
-                Task::new(&path, &name, &formatted_size, 0., 0., true),
-            );
+#[handler]
+async fn files(depot: &Depot, res: &mut Response) -> ServerResult<()> {
+    let session = session::current(depot)?;
+
+    let send_files = match session::files(session).await {
+        Some(files) if !files.is_empty() => files,
+        _ => {
+            error!(message = "未选择文件", session = session);
+            return Err(ServerError::new(
+                "小路互传客户端未选择文件",
+                "请先在小路互传选择一些文件",
+            ));
         }
+    };
 
-        // 上传未完成时删除本地未完成的文件
-        fs::remove_file(&file_path).await.map_err(|e| {
-            error!(message = "删除未完成文件时出错", path = ?file_path, error = ?e);
-            ServerError::Internal
-        })?;
+    debug!(message = "发送的文件列表", session = session, files = ?send_files);
 
-        info!(message = "已删除未完成文件", path = ?file_path);
+    res.render(Json(
+        send_files.iter().map(PhoneFile::from).collect::<Vec<_>>(),
+    ));
 
-        return Err(ServerError::new("请求中断", None));
+    Ok(())
+}
+
+/// 服务实际监听的端口，服务未启动时返回 `None`。
+pub(super) fn port() -> Option<u16> {
+    match PORT.load(Ordering::Acquire) {
+        0 => None,
+        port => Some(port),
     }
+}
 
-    let end = Instant::now();
+/// 监听 IPv6 通配地址并关闭 `IPV6_V6ONLY`，同时接受 IPv4 与 IPv6 连接。
+fn bind_dual_stack(port: u16) -> io::Result<TokioTcpListener> {
+    let socket = Socket::new(Domain::IPV6, Type::STREAM, Some(Protocol::TCP))?;
+    socket.set_only_v6(false)?;
+    // 与 tokio 一致，只在 unix 上设置，Windows 上的 SO_REUSEADDR 允许其他程序抢占端口
+    #[cfg(unix)]
+    socket.set_reuse_address(true)?;
+    socket.bind(&SocketAddr::from((Ipv6Addr::UNSPECIFIED, port)).into())?;
+    socket.listen(1024)?;
+    socket.set_nonblocking(true)?;
+
+    TokioTcpListener::from_std(socket.into())
+}
 
-    info!(
-        message = "已保存文件",
-        path = ?file_path,
-        size = size,
-        ip = ?req.remote_addr(),
-        cost = ?end.duration_since(start),
-    );
+/// 优先同时监听 IPv4 与 IPv6，系统不支持 IPv6 时只监听 IPv4。
+async fn bind_port(port: u16) -> io::Result<TokioTcpListener> {
+    match bind_dual_stack(port) {
+        Ok(listener) => Ok(listener),
+        Err(e) => {
+            debug!(message = "无法同时监听 IPv4 与 IPv6", port = port, error = ?e);
+            TokioTcpListener::bind((Ipv4Addr::UNSPECIFIED, port)).await
+        }
+    }
+}
 
-    Ok(())
+/// 优先监听 `preferred`，被占用时依次尝试其后的端口，最后由系统分配一个空闲端口。
+async fn bind(preferred: u16) -> io::Result<TokioTcpListener> {
+    let candidates = (0..=PORT_SCAN_RANGE)
+        .filter_map(|i| preferred.checked_add(i))
+        .chain(iter::once(0));
+
+    let mut last_error = None;
+    for port in candidates {
+        match bind_port(port).await {
+            Ok(listener) => return Ok(listener),
+            Err(e) => {
+                debug!(message = "端口不可用", port = port, error = ?e);
+                last_error = Some(e);
+            }
+        }
+    }
+
+    Err(last_error.unwrap_or_else(|| io::Error::from(io::ErrorKind::AddrNotAvailable)))
 }
 
 #[derive(RustEmbed)]
 #[folder = "static"]
 struct Assets;
 
-pub(super) async fn serve() -> FluxyResult<()> {
+/// 启动文件传输服务，`tls` 为 `true` 时使用自签名证书提供 HTTPS。
+pub(super) async fn serve(preferred_port: u16, tls: bool) -> FluxyResult<()> {
     // 程序启动时的默认下载目录
     let default_downloads_dir = DOWNLOADS_DIR.read().await;
     if !default_downloads_dir.exists() {
         debug!(message = "创建默认接收目录", dir = ?default_downloads_dir);
-        fs::create_dir(default_downloads_dir.clone())
+        fs::create_dir_all(default_downloads_dir.clone())
             .await
             .map_err(|e| {
                 error!(message = "创建默认接收目录失败", error = ?e);
@@ -350,9 +660,21 @@ pub(super) async fn serve() -> FluxyResult<()> {
     let mut router = Router::new()
         .hoop(Logger::new())
         .push(Router::with_path("connect").get(connect))
-        .push(Router::with_path("files").get(files))
-        .push(Router::with_path("download/<path>").get(download_file))
-        .push(Router::with_path("upload").post(upload));
+        .push(
+            Router::new()
+                .hoop(Auth::new(Mode::Receive))
+                .push(Router::with_path("files").get(files))
+                .push(Router::with_path("download/<id>").get(download_file))
+                .push(Router::with_path("download-all").get(download_all)),
+        )
+        .push(
+            Router::with_path("upload")
+                .hoop(Auth::new(Mode::Send))
+                .head(upload::upload_offset)
+                .post(upload::upload)
+                .patch(upload::resume_upload)
+                .push(Router::with_path("chunk").put(upload::upload_chunk)),
+        );
 
     #[cfg(debug_assertions)]
     {
@@ -366,14 +688,50 @@ pub(super) async fn serve() -> FluxyResult<()> {
         );
     }
 
-    let acceptor = match TcpListener::new("0.0.0.0:5800").try_bind().await {
-        Ok(a) => a,
-        Err(e) => {
-            error!(message = "创建 TcpListener 失败", error = ?e);
-            process::exit(1);
-        }
-    };
-    Server::new(acceptor).serve(router).await;
+    let listener = bind(preferred_port).await.map_err(|e| {
+        error!(message = "创建 TcpListener 失败", error = ?e);
+        e
+    })?;
+
+    // 监听后再清理，不推迟服务启动
+    let downloads_dir = DOWNLOADS_DIR.read().await.clone();
+    tokio::spawn(async move { filename::remove_stale_parts(&downloads_dir).await });
+
+    let port = listener.local_addr()?.port();
+    if port != preferred_port {
+        warn!(
+            message = "首选端口不可用，已改用其他端口",
+            preferred = preferred_port,
+            port = port
+        );
+    }
+
+    if tls {
+        let config = tls::load_or_generate().await?;
+        let acceptor = BoundListener(listener)
+            .rustls(config)
+            .try_bind()
+            .await
+            .map_err(|e| {
+                error!(message = "创建 TLS 监听失败", error = ?e);
+                io::Error::other(e)
+            })?;
+
+        PORT.store(port, Ordering::Release);
+        info!(message = "文件传输服务已启动", port = port, tls = true);
+
+        Server::new(acceptor).serve(router).await;
+    } else {
+        let acceptor = TcpAcceptor::try_from(listener).map_err(|e| {
+            error!(message = "创建 TcpAcceptor 失败", error = ?e);
+            e
+        })?;
+
+        PORT.store(port, Ordering::Release);
+        info!(message = "文件传输服务已启动", port = port, tls = false);
+
+        Server::new(acceptor).serve(router).await;
+    }
 
     Ok(())
 }
@@ -0,0 +1,106 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use salvo::http::StatusCode;
+
+use super::error::{ServerError, ServerResult};
+use super::{format_file_size, session, DOWNLOADS_DIR};
+
+/// 默认在下载目录所在磁盘上保留的空间（字节）
+pub(crate) const DEFAULT_DISK_SPACE_MARGIN: u64 = 512 * 1024 * 1024;
+
+/// 接收文件后磁盘上至少保留的空间（字节）
+static DISK_SPACE_MARGIN: AtomicU64 = AtomicU64::new(DEFAULT_DISK_SPACE_MARGIN);
+/// 单个文件的最大字节数，0 表示不限制
+static MAX_FILE_SIZE: AtomicU64 = AtomicU64::new(0);
+/// 一个会话最多接收的字节数，0 表示不限制
+static MAX_SESSION_SIZE: AtomicU64 = AtomicU64::new(0);
+
+/// 设置磁盘保留空间与大小限制，单位为字节，限制为空时不限制。
+pub(crate) fn set_limits(
+    disk_space_margin: u64,
+    max_file_size: Option<u64>,
+    max_session_size: Option<u64>,
+) {
+    DISK_SPACE_MARGIN.store(disk_space_margin, Ordering::Relaxed);
+    MAX_FILE_SIZE.store(max_file_size.unwrap_or(0), Ordering::Relaxed);
+    MAX_SESSION_SIZE.store(max_session_size.unwrap_or(0), Ordering::Relaxed);
+}
+
+fn limit(value: &AtomicU64) -> Option<u64> {
+    match value.load(Ordering::Relaxed) {
+        0 => None,
+        n => Some(n),
+    }
+}
+
+/// 接收文件前检查大小限制与磁盘剩余空间。
+///
+/// `size` 为文件的总字节数，`remaining` 为还需写入的字节数，续传时小于 `size`。
+pub(super) async fn check(session: u64, size: u64, remaining: u64) -> ServerResult<()> {
+    if let Some(max) = limit(&MAX_FILE_SIZE) {
+        if size > max {
+            error!(message = "文件超过大小限制", size = size, max = max);
+            return Err(ServerError::with_status(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                "文件超过大小限制",
+                format!("电脑端限制单个文件不能超过 {}", format_file_size(max)).as_str(),
+            ));
+        }
+    }
+
+    if let Some(max) = limit(&MAX_SESSION_SIZE) {
+        // 只统计已完成的文件，并发上传的文件之间不互相计入
+        let received = session::transferred_bytes(session).await;
+        if received + size > max {
+            error!(
+                message = "超过会话的大小限制",
+                session = session,
+                received = received,
+                size = size,
+                max = max
+            );
+            return Err(ServerError::with_status(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                "超过本次传输的大小限制",
+                format!(
+                    "电脑端限制每次扫码最多接收 {}，请重新扫码后上传",
+                    format_file_size(max)
+                )
+                .as_str(),
+            ));
+        }
+    }
+
+    let dir = DOWNLOADS_DIR.read().await.clone();
+    let available = match fs4::available_space(&dir) {
+        Ok(n) => n,
+        Err(e) => {
+            // 无法获取时不阻止上传，由写入时的错误处理
+            warn!(message = "获取磁盘剩余空间失败", dir = ?dir, error = ?e);
+            return Ok(());
+        }
+    };
+
+    let margin = DISK_SPACE_MARGIN.load(Ordering::Relaxed);
+    if available < remaining.saturating_add(margin) {
+        error!(
+            message = "磁盘剩余空间不足",
+            dir = ?dir,
+            available = available,
+            remaining = remaining,
+            margin = margin
+        );
+        return Err(ServerError::with_status(
+            StatusCode::INSUFFICIENT_STORAGE,
+            "电脑磁盘空间不足",
+            format!(
+                "此文件需要 {}，电脑仅剩 {} 可用空间",
+                format_file_size(remaining),
+                format_file_size(available.saturating_sub(margin))
+            )
+            .as_str(),
+        ));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,436 @@
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use salvo::http::StatusCode;
+use salvo::{Depot, Request};
+use serde::Serialize;
+use subtle::ConstantTimeEq;
+use tokio::sync::RwLock;
+
+use super::error::{ServerError, ServerResult};
+use super::{transfer, upload};
+use super::{SendFile, SendFilePath, MAIN_WINDOW};
+
+const SESSION_EXPIRED_EVENT: &str = "session://expired";
+
+/// 认证通过后保存在 depot 中的会话 id 的键
+const SESSION_ID: &str = "fluxy_session_id";
+
+/// 默认的未使用二维码有效期（秒）
+pub(crate) const DEFAULT_QR_CODE_TTL: u64 = 5 * 60;
+/// 默认的已连接会话空闲超时（秒）
+pub(crate) const DEFAULT_IDLE_TIMEOUT: u64 = 30 * 60;
+
+/// 检查过期会话的间隔
+const EVICT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// 未使用二维码的有效期（秒）
+static QR_CODE_TTL: AtomicU64 = AtomicU64::new(DEFAULT_QR_CODE_TTL);
+/// 已连接会话在没有请求后的有效期（秒）
+static IDLE_TIMEOUT: AtomicU64 = AtomicU64::new(DEFAULT_IDLE_TIMEOUT);
+
+lazy_static! {
+    static ref SESSIONS: RwLock<HashMap<u64, Session>> = RwLock::new(HashMap::new());
+}
+
+/// 手机端的模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Mode {
+    /// 手机向电脑发送文件
+    Send,
+    /// 手机接收电脑发送的文件
+    Receive,
+}
+
+impl Mode {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            Mode::Send => "send",
+            Mode::Receive => "receive",
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub(crate) struct TransferStats {
+    /// 已传输的文件数
+    files: u64,
+    /// 已传输的字节数
+    bytes: u64,
+}
+
+/// 一个二维码对应的会话
+struct Session {
+    mode: Mode,
+    token: String,
+    /// 手机接收模式下发送的文件列表
+    files: Vec<SendFile>,
+    /// 允许下载的文件，键为文件 id
+    file_paths: HashMap<String, SendFilePath>,
+    /// 下载文件时通知前端使用的传输 id，键为文件 id
+    download_ids: HashMap<String, u64>,
+    /// 扫码连接的客户端地址，连接前为空，连接后只允许此地址访问
+    client: Option<IpAddr>,
+    connected: bool,
+    /// 正在处理的请求数，有请求时会话不会过期
+    active_requests: usize,
+    created_at: SystemTime,
+    /// 连接前为二维码的失效时间，连接后为最后一次请求结束后经过空闲超时的时间
+    expires_at: SystemTime,
+    stats: TransferStats,
+    /// 桌面端已设为始终允许此会话上传
+    trusted: bool,
+    /// 桌面端已确认接收的文件，键为暂存文件路径
+    approved: HashSet<PathBuf>,
+}
+
+impl Session {
+    fn is_expired(&self) -> bool {
+        self.active_requests == 0 && SystemTime::now() >= self.expires_at
+    }
+
+    /// 刷新已连接会话的空闲超时
+    fn touch(&mut self) {
+        self.expires_at = SystemTime::now() + timeout(&IDLE_TIMEOUT);
+    }
+}
+
+fn timeout(secs: &AtomicU64) -> Duration {
+    Duration::from_secs(secs.load(Ordering::Relaxed))
+}
+
+/// 设置未使用二维码的有效期和已连接会话的空闲超时，单位为秒，对之后创建或访问的会话生效。
+pub(crate) fn set_timeouts(qr_code_ttl: u64, idle_timeout: u64) {
+    QR_CODE_TTL.store(qr_code_ttl, Ordering::Relaxed);
+    IDLE_TIMEOUT.store(idle_timeout, Ordering::Relaxed);
+}
+
+/// 前端展示的会话信息
+#[derive(Debug, Serialize)]
+pub(crate) struct SessionInfo {
+    id: u64,
+    mode: Mode,
+    client: Option<IpAddr>,
+    connected: bool,
+    files: usize,
+    /// 创建时间，unix 时间戳（秒）
+    created_at: u64,
+    /// 过期时间，unix 时间戳（秒）
+    expires_at: u64,
+    stats: TransferStats,
+    /// 是否已设为始终允许上传
+    trusted: bool,
+}
+
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// 客户端的 IP，IPv4 映射的 IPv6 地址转换为 IPv4。
+pub(super) fn client_ip(req: &Request) -> Option<IpAddr> {
+    req.remote_addr()
+        .clone()
+        .into_std()
+        .map(|addr| addr.ip().to_canonical())
+}
+
+/// 创建会话，返回会话 id 和令牌。
+///
+/// 手机接收模式下为每个文件生成一个 id，只有会话中的文件才能被下载。
+pub(crate) async fn create(mode: Mode, mut files: Vec<SendFile>) -> (u64, String) {
+    let mut file_paths = HashMap::with_capacity(files.len());
+    for file in files.iter_mut() {
+        file.id = format!("{:032x}", rand::random::<u128>());
+
+        let relative_path = if file.relative_path.is_empty() {
+            file.name.clone()
+        } else {
+            file.relative_path.clone()
+        };
+
+        file_paths.insert(
+            file.id.clone(),
+            SendFilePath {
+                path: file.path.clone(),
+                relative_path,
+            },
+        );
+    }
+
+    let token = format!(
+        "{:032x}{:032x}",
+        rand::random::<u128>(),
+        rand::random::<u128>()
+    );
+
+    let now = SystemTime::now();
+    let mut sessions = SESSIONS.write().await;
+
+    // 毫秒时间戳作为 id，同一毫秒内创建多个会话时递增
+    let mut id = now
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64;
+    while sessions.contains_key(&id) {
+        id += 1;
+    }
+
+    sessions.insert(
+        id,
+        Session {
+            mode,
+            token: token.clone(),
+            files,
+            file_paths,
+            client: None,
+            connected: false,
+            download_ids: HashMap::new(),
+            active_requests: 0,
+            created_at: now,
+            expires_at: now + timeout(&QR_CODE_TTL),
+            stats: TransferStats::default(),
+            trusted: false,
+            approved: HashSet::new(),
+        },
+    );
+
+    info!(message = "已创建会话", id = id, mode = ?mode);
+
+    (id, token)
+}
+
+/// 会话是否已被扫码连接。
+///
+/// 会话不存在时返回 `false`，过期的二维码通过 `session://expired` 事件通知前端。
+pub(crate) async fn is_connected(id: u64) -> bool {
+    SESSIONS.read().await.get(&id).is_some_and(|s| s.connected)
+}
+
+/// 定时删除过期的会话，并通知前端。
+pub(crate) async fn evict_expired() {
+    let mut interval = tokio::time::interval(EVICT_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let mut expired = Vec::new();
+        SESSIONS.write().await.retain(|id, s| {
+            if s.is_expired() {
+                expired.push((*id, s.connected));
+                false
+            } else {
+                true
+            }
+        });
+
+        for (id, connected) in expired {
+            info!(message = "会话已过期", id = id, connected = connected);
+
+            upload::discard_session(id).await;
+
+            if let Some(w) = MAIN_WINDOW.get() {
+                let _ = w.emit(SESSION_EXPIRED_EVENT, id);
+            }
+        }
+    }
+}
+
+/// 所有会话的信息。
+pub(crate) async fn sessions() -> Vec<SessionInfo> {
+    let mut sessions: Vec<SessionInfo> = SESSIONS
+        .read()
+        .await
+        .iter()
+        .map(|(id, s)| SessionInfo {
+            id: *id,
+            mode: s.mode,
+            client: s.client,
+            connected: s.connected,
+            files: s.files.len(),
+            created_at: unix_secs(s.created_at),
+            expires_at: unix_secs(s.expires_at),
+            stats: s.stats.clone(),
+            trusted: s.trusted,
+        })
+        .collect();
+
+    sessions.sort_by_key(|s| s.id);
+
+    sessions
+}
+
+/// 扫码连接会话，令牌匹配时将会话绑定到此客户端，返回会话模式。
+pub(super) async fn connect(req: &Request, id: u64, token: &str) -> ServerResult<Mode> {
+    let mut sessions = SESSIONS.write().await;
+
+    let session = match sessions.get_mut(&id) {
+        Some(s) if !s.is_expired() => s,
+        _ => {
+            error!(message = "会话不存在或已过期", id = id);
+            return Err(ServerError::new(
+                "二维码已失效",
+                "请刷新小路互传页面生成新的二维码",
+            ));
+        }
+    };
+
+    if session.connected {
+        error!(message = "此二维码已被使用", id = id);
+        return Err(ServerError::new(
+            "此二维码已被使用",
+            "请刷新小路互传页面生成新的二维码",
+        ));
+    }
+
+    if !token_matches(&session.token, token) {
+        error!(message = "二维码令牌无效", id = id, ip = ?req.remote_addr());
+        return Err(ServerError::with_status(
+            StatusCode::UNAUTHORIZED,
+            "二维码无效",
+            "请刷新小路互传页面生成新的二维码",
+        ));
+    }
+
+    session.connected = true;
+    session.client = client_ip(req);
+    session.touch();
+
+    info!(message = "会话已连接", id = id, client = ?session.client);
+
+    Ok(session.mode)
+}
+
+/// 以固定时间比较令牌，避免通过响应时间逐字节猜测令牌。
+fn token_matches(expected: &str, token: &str) -> bool {
+    expected.as_bytes().ct_eq(token.as_bytes()).into()
+}
+
+/// 查找令牌对应的会话，会话已连接、未过期、模式匹配且来自绑定的客户端时返回会话 id。
+///
+/// 认证通过后请求处理期间会话不会过期，处理结束后需调用 [`release`]。
+pub(super) async fn authenticate(req: &Request, token: &str, mode: Mode) -> Option<u64> {
+    let client = client_ip(req);
+
+    SESSIONS
+        .write()
+        .await
+        .iter_mut()
+        .find(|(_, s)| {
+            token_matches(&s.token, token)
+                && s.connected
+                && s.mode == mode
+                && s.client == client
+                && !s.is_expired()
+        })
+        .map(|(id, s)| {
+            s.active_requests += 1;
+            s.touch();
+            *id
+        })
+}
+
+/// 响应体在处理函数返回后才发送完，发送期间保持会话活跃，发送结束后调用 [`release`]。
+pub(super) async fn hold(id: u64) {
+    if let Some(s) = SESSIONS.write().await.get_mut(&id) {
+        s.active_requests += 1;
+        s.touch();
+    }
+}
+
+/// 请求处理结束，从此时开始计算空闲超时。
+pub(super) async fn release(id: u64) {
+    if let Some(s) = SESSIONS.write().await.get_mut(&id) {
+        s.active_requests = s.active_requests.saturating_sub(1);
+        s.touch();
+    }
+}
+
+/// 将认证通过的会话 id 保存到 depot 中。
+pub(super) fn set_current(depot: &mut Depot, id: u64) {
+    depot.insert(SESSION_ID, id);
+}
+
+/// 当前请求所属的会话 id。
+pub(super) fn current(depot: &Depot) -> ServerResult<u64> {
+    depot.get::<u64>(SESSION_ID).ok().copied().ok_or_else(|| {
+        error!("depot 中未找到会话 id");
+        ServerError::Internal
+    })
+}
+
+/// 会话中发送的文件列表。
+pub(super) async fn files(id: u64) -> Option<Vec<SendFile>> {
+    SESSIONS.read().await.get(&id).map(|s| s.files.clone())
+}
+
+/// 会话中 id 为 `file_id` 的文件路径。
+pub(super) async fn file_path(id: u64, file_id: &str) -> Option<PathBuf> {
+    SESSIONS
+        .read()
+        .await
+        .get(&id)
+        .and_then(|s| s.file_paths.get(file_id))
+        .map(|p| p.path.clone())
+}
+
+/// 下载文件使用的传输 id，同一会话中同一文件的多个范围请求使用同一个 id。
+pub(super) async fn download_id(id: u64, file_id: &str) -> u64 {
+    match SESSIONS.write().await.get_mut(&id) {
+        Some(s) => *s
+            .download_ids
+            .entry(file_id.to_owned())
+            .or_insert_with(transfer::next_id),
+        None => transfer::next_id(),
+    }
+}
+
+/// 会话中的所有文件路径。
+pub(super) async fn file_paths(id: u64) -> Vec<SendFilePath> {
+    SESSIONS
+        .read()
+        .await
+        .get(&id)
+        .map(|s| s.file_paths.values().cloned().collect())
+        .unwrap_or_default()
+}
+
+/// 会话中已完成传输的字节数。
+pub(super) async fn transferred_bytes(id: u64) -> u64 {
+    SESSIONS
+        .read()
+        .await
+        .get(&id)
+        .map(|s| s.stats.bytes)
+        .unwrap_or(0)
+}
+
+/// 记录会话中完成传输的文件。
+pub(super) async fn record_transfer(id: u64, files: u64, bytes: u64) {
+    if let Some(s) = SESSIONS.write().await.get_mut(&id) {
+        s.stats.files += files;
+        s.stats.bytes += bytes;
+    }
+}
+
+/// 会话是否已被设为始终允许上传，或 `key` 对应的文件已确认接收。
+pub(super) async fn is_approved(id: u64, key: &Path) -> bool {
+    SESSIONS
+        .read()
+        .await
+        .get(&id)
+        .is_some_and(|s| s.trusted || s.approved.contains(key))
+}
+
+/// 记录桌面端确认接收的文件，`always` 为 `true` 时此会话之后的上传不再需要确认。
+pub(super) async fn approve(id: u64, key: PathBuf, always: bool) {
+    if let Some(s) = SESSIONS.write().await.get_mut(&id) {
+        s.approved.insert(key);
+        s.trusted |= always;
+    }
+}
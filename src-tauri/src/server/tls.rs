@@ -0,0 +1,121 @@
+use std::io::{self, Cursor};
+use std::path::Path;
+use std::sync::OnceLock;
+
+use salvo::conn::rustls::{Keycert, RustlsConfig};
+use salvo::conn::{Listener, TcpAcceptor};
+use sha2::{Digest, Sha256};
+use tokio::fs::{self, OpenOptions};
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpListener as TokioTcpListener;
+
+use crate::error::FluxyResult;
+use crate::lazy::APP_CONFIG_DIR;
+use crate::network;
+
+const TLS_DIR: &str = "tls";
+const CERT_FILE: &str = "cert.pem";
+const KEY_FILE: &str = "key.pem";
+
+/// 当前使用的证书的 SHA-256 指纹，未启用 HTTPS 时为空
+static FINGERPRINT: OnceLock<String> = OnceLock::new();
+
+/// 当前使用的证书指纹，冒号分隔的大写十六进制，未启用 HTTPS 时返回 `None`。
+pub(crate) fn fingerprint() -> Option<&'static str> {
+    FINGERPRINT.get().map(String::as_str)
+}
+
+/// 已绑定端口的监听器，用于在其上启用 rustls。
+pub(super) struct BoundListener(pub(super) TokioTcpListener);
+
+impl Listener for BoundListener {
+    type Acceptor = TcpAcceptor;
+
+    async fn try_bind(self) -> salvo::Result<Self::Acceptor> {
+        Ok(TcpAcceptor::try_from(self.0)?)
+    }
+}
+
+/// 读取 `APP_CONFIG_DIR` 中的证书，不存在时生成自签名证书并保存。
+pub(super) async fn load_or_generate() -> FluxyResult<RustlsConfig> {
+    let dir = APP_CONFIG_DIR.join(TLS_DIR);
+    let cert_path = dir.join(CERT_FILE);
+    let key_path = dir.join(KEY_FILE);
+
+    let (cert, key) = match (
+        fs::read_to_string(&cert_path).await,
+        fs::read_to_string(&key_path).await,
+    ) {
+        (Ok(cert), Ok(key)) => {
+            debug!(message = "已读取证书", path = ?cert_path);
+            (cert, key)
+        }
+        _ => generate(&dir, &cert_path, &key_path).await?,
+    };
+
+    let fingerprint = certificate_fingerprint(&cert)?;
+    info!(message = "已启用 HTTPS", fingerprint = fingerprint);
+    let _ = FINGERPRINT.set(fingerprint);
+
+    Ok(RustlsConfig::new(
+        Keycert::new().cert(cert.into_bytes()).key(key.into_bytes()),
+    ))
+}
+
+/// 生成包含 localhost 与本机所有地址的自签名证书。
+async fn generate(dir: &Path, cert_path: &Path, key_path: &Path) -> FluxyResult<(String, String)> {
+    let mut names = vec!["localhost".to_owned()];
+    names.extend(
+        network::interfaces()
+            .iter()
+            .flat_map(|i| i.addresses())
+            .map(|ip| ip.to_string()),
+    );
+
+    debug!(message = "生成自签名证书", names = ?names);
+
+    let certified = rcgen::generate_simple_self_signed(names).map_err(|e| {
+        error!(message = "生成自签名证书失败", error = ?e);
+        e
+    })?;
+
+    let cert = certified.cert.pem();
+    let key = certified.key_pair.serialize_pem();
+
+    fs::create_dir_all(dir).await?;
+    fs::write(cert_path, &cert).await?;
+
+    // 私钥只允许当前用户读写
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let mut file = options.open(key_path).await.map_err(|e| {
+        error!(message = "保存私钥失败", path = ?key_path, error = ?e);
+        e
+    })?;
+    file.write_all(key.as_bytes()).await?;
+    file.sync_all().await?;
+
+    info!(message = "已生成自签名证书", path = ?cert_path);
+
+    Ok((cert, key))
+}
+
+fn certificate_fingerprint(cert: &str) -> FluxyResult<String> {
+    let der = rustls_pemfile::certs(&mut Cursor::new(cert))
+        .next()
+        .unwrap_or_else(|| Err(io::Error::new(io::ErrorKind::InvalidData, "证书文件为空")))
+        .map_err(|e| {
+            error!(message = "解析证书失败", error = ?e);
+            e
+        })?;
+
+    let digest = Sha256::digest(der.as_ref());
+
+    Ok(digest
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<_>>()
+        .join(":"))
+}
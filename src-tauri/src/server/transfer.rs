@@ -0,0 +1,147 @@
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind, Result};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::watch;
+use tokio_util::sync::CancellationToken;
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+lazy_static! {
+    /// 正在进行的传输，键为传输 id
+    static ref TRANSFERS: Mutex<HashMap<u64, Arc<TransferControl>>> = Mutex::new(HashMap::new());
+}
+
+/// 桌面端对一个传输的控制
+pub(super) struct TransferControl {
+    cancel: CancellationToken,
+    paused: watch::Sender<bool>,
+}
+
+impl TransferControl {
+    fn new() -> Self {
+        Self {
+            cancel: CancellationToken::new(),
+            paused: watch::Sender::new(false),
+        }
+    }
+
+    pub(super) fn is_cancelled(&self) -> bool {
+        self.cancel.is_cancelled()
+    }
+
+    /// 暂停时等待恢复
+    async fn wait_if_paused(&self) {
+        let mut paused = self.paused.subscribe();
+        let _ = paused.wait_for(|p| !*p).await;
+    }
+
+    /// 将 `reader` 复制到 `writer`，返回复制的字节数。
+    ///
+    /// 暂停期间不再读取，客户端因此也会停止发送；取消时返回 [`ErrorKind::Interrupted`] 错误。
+    pub(super) async fn copy<R, W>(&self, reader: &mut R, writer: &mut W) -> Result<u64>
+    where
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin,
+    {
+        let copy = async {
+            let mut buf = vec![0; 64 * 1024];
+            let mut copied = 0;
+
+            loop {
+                self.wait_if_paused().await;
+
+                let n = reader.read(&mut buf).await?;
+                if n == 0 {
+                    return Ok(copied);
+                }
+
+                writer.write_all(&buf[..n]).await?;
+                copied += n as u64;
+            }
+        };
+
+        tokio::select! {
+            r = copy => r,
+            _ = self.cancel.cancelled() => Err(Error::new(ErrorKind::Interrupted, "传输已取消")),
+        }
+    }
+}
+
+/// 已注册的传输，离开作用域时注销
+pub(super) struct Transfer {
+    id: u64,
+    control: Arc<TransferControl>,
+}
+
+/// 生成新的传输 id，与已注册的传输共用同一序列。
+pub(super) fn next_id() -> u64 {
+    NEXT_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+impl Transfer {
+    pub(super) fn register() -> Self {
+        let id = next_id();
+        let control = Arc::new(TransferControl::new());
+
+        TRANSFERS.lock().unwrap().insert(id, control.clone());
+
+        Self { id, control }
+    }
+
+    pub(super) fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub(super) fn control(&self) -> Arc<TransferControl> {
+        self.control.clone()
+    }
+}
+
+impl Drop for Transfer {
+    fn drop(&mut self) {
+        TRANSFERS.lock().unwrap().remove(&self.id);
+    }
+}
+
+fn control(id: u64) -> Option<Arc<TransferControl>> {
+    TRANSFERS.lock().unwrap().get(&id).cloned()
+}
+
+/// 取消传输，传输不存在时返回 `false`。
+pub(crate) fn cancel(id: u64) -> bool {
+    match control(id) {
+        Some(c) => {
+            c.cancel.cancel();
+            info!(message = "已取消传输", id = id);
+            true
+        }
+        None => false,
+    }
+}
+
+/// 暂停传输，传输不存在时返回 `false`。
+pub(crate) fn pause(id: u64) -> bool {
+    match control(id) {
+        Some(c) => {
+            c.paused.send_replace(true);
+            info!(message = "已暂停传输", id = id);
+            true
+        }
+        None => false,
+    }
+}
+
+/// 恢复暂停的传输，传输不存在时返回 `false`。
+pub(crate) fn resume(id: u64) -> bool {
+    match control(id) {
+        Some(c) => {
+            c.paused.send_replace(false);
+            info!(message = "已恢复传输", id = id);
+            true
+        }
+        None => false,
+    }
+}
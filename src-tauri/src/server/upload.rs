@@ -0,0 +1,1121 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::SeekFrom;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use salvo::http::header::HeaderName;
+use salvo::prelude::*;
+use sha2::{Digest, Sha256};
+use tokio::fs::{self, File, OpenOptions};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio_util::io::StreamReader;
+
+use crate::history::{Direction, Outcome, Record};
+use crate::stream::{mb_per_sec, progress_interval, Progress, ReadProgressStream, SpeedWindow};
+use crate::throttle::TransferBucket;
+
+use super::error::{ServerError, ServerResult};
+use super::filename::{
+    part_path, persist, preview_destination, sanitize_filename, sanitize_relative_path,
+    track_part_dir,
+};
+use super::transfer::Transfer;
+use super::{approval, quota, session};
+use super::{format_file_size, Task, CONFLICT_POLICY, DOWNLOADS_DIR, MAIN_WINDOW, UPLOAD_EVENT};
+
+/// 已接收的字节数
+const UPLOAD_OFFSET: HeaderName = HeaderName::from_static("upload-offset");
+/// 文件的总字节数
+const UPLOAD_LENGTH: HeaderName = HeaderName::from_static("upload-length");
+/// 客户端计算的整个文件的 SHA-256，十六进制
+const CONTENT_SHA256: HeaderName = HeaderName::from_static("x-content-sha256");
+
+lazy_static! {
+    /// 正在写入的暂存文件，同一个暂存文件同时只允许一个请求写入
+    static ref ACTIVE_UPLOADS: Mutex<HashSet<PathBuf>> = Mutex::new(HashSet::new());
+    /// 正在进行的分块上传，键为暂存文件路径
+    static ref CHUNKED_UPLOADS: tokio::sync::Mutex<HashMap<PathBuf, ChunkedUpload>> =
+        tokio::sync::Mutex::new(HashMap::new());
+    /// 被桌面端取消的分块上传，键为暂存文件路径，值为会话 id。
+    ///
+    /// 之后到达的分块直接拒绝，不会重新开始上传，会话过期时清除。
+    static ref CANCELLED_CHUNKED_UPLOADS: Mutex<HashMap<PathBuf, u64>> = Mutex::new(HashMap::new());
+}
+
+/// 持有期间独占一个暂存文件，释放时自动解除占用
+struct ActiveUpload(PathBuf);
+
+impl ActiveUpload {
+    fn acquire(path: &Path) -> Option<Self> {
+        let mut active = ACTIVE_UPLOADS.lock().unwrap();
+        if active.insert(path.to_path_buf()) {
+            Some(Self(path.to_path_buf()))
+        } else {
+            None
+        }
+    }
+}
+
+impl Drop for ActiveUpload {
+    fn drop(&mut self) {
+        ACTIVE_UPLOADS.lock().unwrap().remove(&self.0);
+    }
+}
+
+/// 上传文件在下载目录中的位置
+struct Target {
+    /// 相对于下载目录的各级文件夹，上传单个文件时为空
+    folders: Vec<String>,
+    name: String,
+}
+
+impl Target {
+    async fn dir(&self) -> PathBuf {
+        let mut dir = DOWNLOADS_DIR.read().await.to_path_buf();
+        dir.extend(&self.folders);
+        dir
+    }
+
+    /// 上传文件夹时文件所属的顶层文件夹
+    fn folder(&self) -> Option<&str> {
+        self.folders.first().map(|s| s.as_str())
+    }
+}
+
+/// 从查询参数中读取上传位置。
+///
+/// 上传文件夹时使用 `path` 传递文件相对于所选文件夹的路径，否则使用 `name` 传递文件名。
+fn query_target(req: &Request) -> ServerResult<Target> {
+    if let Some(path) = req.query::<String>("path") {
+        return match sanitize_relative_path(&path) {
+            Some(mut folders) => {
+                let name = folders.pop().unwrap();
+                Ok(Target { folders, name })
+            }
+            None => {
+                error!(message = "文件路径无效", path = path);
+                Err(ServerError::new(
+                    "文件路径无效",
+                    "请修改文件夹结构后重新上传",
+                ))
+            }
+        };
+    }
+
+    let name = match req.query::<String>("name") {
+        Some(s) => s,
+        None => {
+            error!("请求地址中未找到文件名");
+            return Err(ServerError::new("文件名为空", "请通过小路互传扫码访问"));
+        }
+    };
+
+    match sanitize_filename(&name) {
+        Some(name) => Ok(Target {
+            folders: Vec::new(),
+            name,
+        }),
+        None => {
+            error!(message = "文件名无效", name = name);
+            Err(ServerError::new("文件名无效", "请修改文件名后重新上传"))
+        }
+    }
+}
+
+async fn create_dir(dir: &Path) -> ServerResult<()> {
+    fs::create_dir_all(dir).await.map_err(|e| {
+        error!(message = "创建接收目录失败", dir = ?dir, error = ?e);
+        ServerError::Internal
+    })
+}
+
+/// 前端任务列表中显示的上传文件信息
+#[derive(Clone)]
+struct TaskInfo {
+    id: u64,
+    path: PathBuf,
+    name: String,
+    size: u64,
+    formatted_size: String,
+    folder: Option<String>,
+    /// 传输结束时写入的历史记录
+    record: Record,
+}
+
+impl TaskInfo {
+    fn new(
+        id: u64,
+        path: PathBuf,
+        target: &Target,
+        size: u64,
+        peer: Option<IpAddr>,
+        start: Instant,
+    ) -> Self {
+        Self {
+            id,
+            path,
+            name: target.name.clone(),
+            size,
+            formatted_size: format_file_size(size),
+            folder: target.folder().map(|s| s.to_owned()),
+            record: Record::new(Direction::Upload, target.name.as_str(), size, peer)
+                .started_at(start),
+        }
+    }
+
+    fn task(&self, percent: f64, speed: f64, aborted: bool) -> Task<'_> {
+        Task::new(
+            self.id,
+            &self.path,
+            &self.name,
+            &self.formatted_size,
+            percent,
+            speed,
+            aborted,
+        )
+        .in_folder(self.folder.as_deref())
+    }
+
+    fn emit(&self, percent: f64, speed: f64, aborted: bool) {
+        if let Some(w) = MAIN_WINDOW.get() {
+            let _ = w.emit(UPLOAD_EVENT, self.task(percent, speed, aborted));
+        }
+    }
+
+    /// 通知前端已接收 `received` 个字节。
+    fn emit_progress(&self, received: u64, speed: f64, average_speed: f64) {
+        let percent = if self.size == 0 {
+            100.
+        } else {
+            (received.min(self.size) * 1000 / self.size) as f64 / 10.0
+        };
+
+        if let Some(w) = MAIN_WINDOW.get() {
+            let task =
+                self.task(percent, speed, false)
+                    .with_progress(received, self.size, average_speed);
+            let _ = w.emit(UPLOAD_EVENT, task);
+        }
+    }
+}
+
+fn header_u64(req: &Request, name: &HeaderName) -> ServerResult<u64> {
+    match req.header::<u64>(name.as_str()) {
+        Some(n) => Ok(n),
+        None => {
+            error!(message = "请求头中未找到有效的数值", header = name.as_str());
+            Err(ServerError::new(
+                "请求头缺失或无效",
+                "请通过小路互传扫码访问",
+            ))
+        }
+    }
+}
+
+async fn staged_len(path: &Path) -> u64 {
+    fs::metadata(path).await.map(|m| m.len()).unwrap_or(0)
+}
+
+fn expected_hash(req: &Request) -> Option<String> {
+    req.header::<String>(CONTENT_SHA256.as_str())
+        .map(|s| s.trim().to_ascii_lowercase())
+}
+
+/// 计算文件前 `len` 个字节的哈希。
+async fn hash_file(path: &Path, len: u64) -> ServerResult<Sha256> {
+    let mut hasher = Sha256::new();
+
+    let file = File::open(path).await.map_err(|e| {
+        error!(message = "打开暂存文件时出错", path = ?path, error = ?e);
+        ServerError::Internal
+    })?;
+    let mut reader = file.take(len);
+    let mut buf = vec![0; 64 * 1024];
+
+    loop {
+        let n = reader.read(&mut buf).await.map_err(|e| {
+            error!(message = "读取暂存文件时出错", path = ?path, error = ?e);
+            ServerError::Internal
+        })?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+
+    Ok(hasher)
+}
+
+/// 校验接收完整的暂存文件的哈希。
+///
+/// 不一致时删除暂存文件、记录失败并通知前端，一致时返回十六进制的哈希。
+/// `received` 为本次请求接收的字节数。
+async fn verify_hash(
+    staging: &Path,
+    info: &TaskInfo,
+    expected: &str,
+    hasher: Sha256,
+    received: u64,
+) -> ServerResult<String> {
+    let actual = format!("{:x}", hasher.finalize());
+
+    if actual == expected {
+        debug!(message = "文件校验通过", name = info.name, hash = actual);
+        return Ok(actual);
+    }
+
+    error!(
+        message = "文件校验失败",
+        name = info.name,
+        expected = expected,
+        actual = actual
+    );
+
+    info.emit(0., 0., true);
+    info.record
+        .finish(Outcome::Failed, None, Some(&actual), received)
+        .await;
+
+    fs::remove_file(staging).await.map_err(|e| {
+        error!(message = "删除暂存文件时出错", path = ?staging, error = ?e);
+        ServerError::Internal
+    })?;
+
+    Err(ServerError::with_status(
+        StatusCode::UNPROCESSABLE_ENTITY,
+        "文件校验失败",
+        "文件在传输过程中损坏，请重新上传此文件",
+    ))
+}
+
+/// 桌面端取消了传输，删除未完成文件、记录取消并通知前端。
+async fn discard_cancelled(staging: &Path, info: &TaskInfo, received: u64) -> ServerError {
+    info!(message = "传输已被桌面端取消", name = info.name, path = ?staging);
+
+    info.emit(0., 0., true);
+    info.record
+        .finish(Outcome::Cancelled, None, None, received)
+        .await;
+
+    if let Err(e) = fs::remove_file(staging).await {
+        error!(message = "删除暂存文件时出错", path = ?staging, error = ?e);
+    }
+
+    cancelled_error()
+}
+
+fn cancelled_error() -> ServerError {
+    ServerError::with_status(StatusCode::GONE, "传输已被取消", "电脑端取消了此文件的传输")
+}
+
+/// 通知前端文件已保存，前端按 id 将接收中的任务替换为实际保存的路径。
+fn emit_saved(info: &TaskInfo, saved: &Path, speed: f64, hash: Option<&str>) {
+    let w = match MAIN_WINDOW.get() {
+        Some(w) => w,
+        None => return,
+    };
+
+    let mut task = Task::new(
+        info.id,
+        saved,
+        &info.name,
+        &info.formatted_size,
+        100.,
+        speed,
+        false,
+    )
+    .with_progress(info.size, info.size, speed)
+    .in_folder(info.folder.as_deref());
+    if let Some(hash) = hash {
+        task = task.with_hash(hash);
+    }
+    let _ = w.emit(UPLOAD_EVENT, task);
+}
+
+/// 查询已接收的字节数，客户端据此决定从哪里继续上传。
+#[handler]
+pub(super) async fn upload_offset(
+    req: &mut Request,
+    depot: &Depot,
+    res: &mut Response,
+) -> ServerResult<()> {
+    let session = session::current(depot)?;
+
+    let target = query_target(req)?;
+    let size = header_u64(req, &UPLOAD_LENGTH)?;
+
+    let staging = part_path(&target.dir().await, &target.name, size, session, false);
+    let offset = staged_len(&staging).await;
+
+    debug!(
+        message = "查询上传进度",
+        name = target.name,
+        offset = offset,
+        size = size
+    );
+
+    res.headers_mut().insert(UPLOAD_OFFSET, offset.into());
+    res.headers_mut().insert(UPLOAD_LENGTH, size.into());
+
+    Ok(())
+}
+
+/// 一次性上传整个文件。
+///
+/// 请求中断时已接收的部分保留在未完成文件中，可通过 `PATCH` 继续上传。
+#[handler]
+pub(super) async fn upload(
+    req: &mut Request,
+    depot: &Depot,
+    res: &mut Response,
+) -> ServerResult<()> {
+    debug!(message = "收到上传任务", ip = ?req.remote_addr());
+
+    let session = session::current(depot)?;
+
+    let target = query_target(req)?;
+    debug!(message = "接收的文件名", name = target.name, folders = ?target.folders);
+
+    let size: u64 = match req.header("content-length") {
+        Some(n) => n,
+        None => {
+            error!("请求头中未找到文件大小");
+
+            return Err(ServerError::new("文件长度为空", "请通过小路互传扫码访问"));
+        }
+    };
+
+    receive(req, res, session, target, 0, size).await
+}
+
+/// 从 `Upload-Offset` 处继续上传文件。
+#[handler]
+pub(super) async fn resume_upload(
+    req: &mut Request,
+    depot: &Depot,
+    res: &mut Response,
+) -> ServerResult<()> {
+    debug!(message = "收到续传任务", ip = ?req.remote_addr());
+
+    let session = session::current(depot)?;
+
+    let target = query_target(req)?;
+    let offset = header_u64(req, &UPLOAD_OFFSET)?;
+    let size = header_u64(req, &UPLOAD_LENGTH)?;
+
+    let staging = part_path(&target.dir().await, &target.name, size, session, false);
+    let staged = staged_len(&staging).await;
+    if staged != offset {
+        error!(
+            message = "续传位置与已接收的字节数不一致",
+            name = target.name,
+            offset = offset,
+            staged = staged
+        );
+
+        res.headers_mut().insert(UPLOAD_OFFSET, staged.into());
+
+        // 与文件名冲突的 409 区分，客户端只对续传位置错误和文件占用重试
+        return Err(ServerError::with_status(
+            StatusCode::PRECONDITION_FAILED,
+            "续传位置错误",
+            "请重新查询已上传的字节数",
+        ));
+    }
+
+    receive(req, res, session, target, offset, size).await
+}
+
+/// 将请求体从 `offset` 处写入暂存文件，接收完整后移动到下载目录。
+async fn receive(
+    req: &mut Request,
+    res: &mut Response,
+    session: u64,
+    target: Target,
+    offset: u64,
+    size: u64,
+) -> ServerResult<()> {
+    info!(
+        message= "收到有效的上传任务",
+        name = target.name,
+        folders = ?target.folders,
+        offset = offset,
+        size = size,
+        ip = ?req.remote_addr()
+    );
+
+    let dir = target.dir().await;
+    let staging = part_path(&dir, &target.name, size, session, false);
+
+    quota::check(session, size, size.saturating_sub(offset)).await?;
+    approval::request(req, session, &staging, &target.name, target.folder(), size).await?;
+
+    let _active = match ActiveUpload::acquire(&staging) {
+        Some(a) => a,
+        None => {
+            error!(message = "文件正在被其他请求上传", name = target.name);
+            return Err(ServerError::with_status(
+                StatusCode::LOCKED,
+                "文件正在上传",
+                "请等待当前上传结束后再试",
+            ));
+        }
+    };
+
+    let start = Instant::now();
+
+    let transfer = Transfer::register();
+    let control = transfer.control();
+
+    let policy = *CONFLICT_POLICY.read().await;
+    // 接收过程中按预测的保存路径显示进度
+    let info = TaskInfo::new(
+        transfer.id(),
+        preview_destination(&dir, &target.name, policy)?,
+        &target,
+        size,
+        session::client_ip(req),
+        start,
+    );
+
+    create_dir(&dir).await?;
+    if !target.folders.is_empty() {
+        track_part_dir(&dir).await;
+    }
+
+    let expected_hash = expected_hash(req);
+
+    let body = req.take_body();
+    let mut stream = ReadProgressStream::new(
+        body,
+        Box::new({
+            let info = info.clone();
+            move |progress: Progress| {
+                info.emit_progress(
+                    offset + progress.bytes,
+                    progress.speed,
+                    progress.average_speed,
+                );
+            }
+        }),
+    )
+    .with_len(size.saturating_sub(offset));
+
+    if expected_hash.is_some() {
+        // 续传时先计算已接收部分的哈希，再继续计算本次接收的数据
+        let hasher = if offset > 0 {
+            hash_file(&staging, offset).await?
+        } else {
+            Sha256::new()
+        };
+        stream = stream.with_hasher(hasher);
+    }
+
+    debug!(message = "暂存文件路径", path = ?staging);
+
+    let mut stream_reader = StreamReader::new(stream);
+    let mut file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(offset > 0)
+        .truncate(offset == 0)
+        .open(&staging)
+        .await
+        .map_err(|e| {
+            error!(message = "打开暂存文件时出错", path = ?staging, error = ?e);
+            ServerError::Internal
+        })?;
+    let copied = control.copy(&mut stream_reader, &mut file).await;
+    // 中断时也要确保已接收的数据全部写入暂存文件
+    if let Err(e) = file.flush().await {
+        error!(message = "写入暂存文件时出错", path = ?staging, error = ?e);
+    }
+    drop(file);
+
+    let received = staged_len(&staging).await;
+
+    if control.is_cancelled() {
+        return Err(discard_cancelled(&staging, &info, received.saturating_sub(offset)).await);
+    }
+
+    res.headers_mut().insert(UPLOAD_OFFSET, received.into());
+
+    if let Err(e) = copied {
+        error!(message = "复制文件流时出错", path = ?staging, error = ?e);
+
+        info.emit(0., 0., true);
+        info.record
+            .finish(
+                Outcome::Interrupted,
+                None,
+                None,
+                received.saturating_sub(offset),
+            )
+            .await;
+
+        // 保留未完成的文件，客户端可以从中断处继续上传
+        info!(message = "已保留未完成文件", path = ?staging, received = received);
+
+        return Err(ServerError::new("请求中断", None));
+    }
+
+    if received < size {
+        info!(message = "已接收部分文件", path = ?staging, received = received, size = size);
+        return Ok(());
+    }
+
+    if received > size {
+        error!(message = "接收的字节数超过文件大小", path = ?staging, received = received, size = size);
+
+        info.record
+            .finish(Outcome::Failed, None, None, received - offset)
+            .await;
+
+        fs::remove_file(&staging).await.map_err(|e| {
+            error!(message = "删除暂存文件时出错", path = ?staging, error = ?e);
+            ServerError::Internal
+        })?;
+
+        return Err(ServerError::new("文件长度错误", "请重新上传此文件"));
+    }
+
+    let hash = match (expected_hash, stream_reader.into_inner().into_hasher()) {
+        (Some(expected), Some(hasher)) => {
+            Some(verify_hash(&staging, &info, &expected, hasher, size - offset).await?)
+        }
+        _ => None,
+    };
+
+    let saved = persist(&staging, &dir, &target.name, policy).await?;
+
+    let end = Instant::now();
+    let cost = end.duration_since(start);
+
+    let speed = mb_per_sec(size - offset, cost);
+    emit_saved(&info, &saved, speed, hash.as_deref());
+    session::record_transfer(session, 1, size).await;
+    info.record
+        .finish(
+            Outcome::Completed,
+            Some(&saved),
+            hash.as_deref(),
+            size - offset,
+        )
+        .await;
+
+    info!(
+        message = "已保存文件",
+        path = ?saved,
+        size = size,
+        hash = ?hash,
+        ip = ?req.remote_addr(),
+        cost = ?cost,
+    );
+
+    Ok(())
+}
+
+/// 一个分块上传的文件的接收状态
+struct ChunkedUpload {
+    session: u64,
+    /// 所有分块共用一个传输，桌面端取消或暂停时作用于整个文件
+    transfer: Transfer,
+    info: TaskInfo,
+    start: Instant,
+    /// 所有分块已接收的字节数之和，包括正在接收的分块
+    received: Arc<AtomicU64>,
+    /// 所有分块共用，按整个文件的接收进度计算速度和合并通知
+    progress: Arc<Mutex<ChunkProgress>>,
+    /// 所有分块共用，单个传输的限速作用于整个文件
+    bucket: TransferBucket,
+    /// 已完整接收的分块，键为起始位置，值为结束位置（不含）
+    completed: BTreeMap<u64, u64>,
+    /// 正在接收的分块，与 `completed` 的格式相同
+    receiving: BTreeMap<u64, u64>,
+    /// 客户端通过任一分块提供的整个文件的哈希
+    expected_hash: Option<String>,
+}
+
+impl ChunkedUpload {
+    fn new(session: u64, transfer: Transfer, info: TaskInfo, start: Instant) -> Self {
+        Self {
+            session,
+            transfer,
+            info,
+            start,
+            received: Arc::new(AtomicU64::new(0)),
+            progress: Arc::new(Mutex::new(ChunkProgress::new(start))),
+            bucket: TransferBucket::new(),
+            completed: BTreeMap::new(),
+            receiving: BTreeMap::new(),
+            expected_hash: None,
+        }
+    }
+
+    /// 已完整接收的字节数，分块之间互不重叠
+    fn completed_len(&self) -> u64 {
+        self.completed.iter().map(|(first, end)| end - first).sum()
+    }
+}
+
+/// 分块上传的进度通知状态。
+///
+/// 每个分块的流各自按间隔报告进度，并发上传时通知次数会成倍增加，
+/// 所以按整个文件上一次通知的时间合并，只在超过间隔或文件接收完毕时通知前端。
+struct ChunkProgress {
+    speed: SpeedWindow,
+    /// 上一次通知的时间与已接收的字节数
+    last_notified: Option<(Instant, u64)>,
+}
+
+impl ChunkProgress {
+    fn new(start: Instant) -> Self {
+        let mut speed = SpeedWindow::new();
+        speed.update(start, 0);
+
+        Self {
+            speed,
+            last_notified: None,
+        }
+    }
+
+    /// 需要通知时记录本次通知并返回速度，否则返回 `None`。
+    fn update(&mut self, now: Instant, received: u64, size: u64) -> Option<f64> {
+        let due = match self.last_notified {
+            None => true,
+            Some((_, last_received)) if received >= size => received != last_received,
+            Some((time, _)) => now.duration_since(time) >= progress_interval(),
+        };
+        if !due {
+            return None;
+        }
+
+        self.last_notified = Some((now, received));
+        Some(self.speed.update(now, received))
+    }
+}
+
+/// `[first, end)` 是否与 `ranges` 中的任一范围重叠，`ranges` 中的范围互不重叠。
+fn overlaps(ranges: &BTreeMap<u64, u64>, first: u64, end: u64) -> bool {
+    ranges
+        .range(..end)
+        .next_back()
+        .is_some_and(|(_, &last_end)| last_end > first)
+}
+
+/// 会话过期后删除其未完成的分块上传。
+///
+/// 分块的接收状态只保存在内存中，无法续传，所以同时删除暂存文件。
+pub(super) async fn discard_session(session: u64) {
+    let discarded: Vec<(PathBuf, ChunkedUpload)> = {
+        let mut uploads = CHUNKED_UPLOADS.lock().await;
+        let keys: Vec<PathBuf> = uploads
+            .iter()
+            .filter(|(_, u)| u.session == session)
+            .map(|(k, _)| k.clone())
+            .collect();
+
+        keys.into_iter()
+            .filter_map(|k| uploads.remove_entry(&k))
+            .collect()
+    };
+
+    CANCELLED_CHUNKED_UPLOADS
+        .lock()
+        .unwrap()
+        .retain(|_, s| *s != session);
+
+    for (staging, upload) in discarded {
+        info!(message = "会话已过期，删除未完成的分块上传", session = session, path = ?staging);
+
+        upload.info.emit(0., 0., true);
+        upload
+            .info
+            .record
+            .finish(
+                Outcome::Interrupted,
+                None,
+                None,
+                upload.received.load(Ordering::Relaxed),
+            )
+            .await;
+
+        if let Err(e) = fs::remove_file(&staging).await {
+            error!(message = "删除暂存文件时出错", path = ?staging, error = ?e);
+        }
+    }
+}
+
+/// 解析 `bytes <first>-<last>/<size>` 格式的 `Content-Range`。
+pub(super) fn parse_content_range(value: &str) -> Option<(u64, u64, u64)> {
+    let (range, size) = value.strip_prefix("bytes ")?.split_once('/')?;
+    let (first, last) = range.split_once('-')?;
+
+    let first: u64 = first.trim().parse().ok()?;
+    let last: u64 = last.trim().parse().ok()?;
+    let size: u64 = size.trim().parse().ok()?;
+
+    if first > last || last >= size {
+        return None;
+    }
+
+    Some((first, last, size))
+}
+
+/// 接收文件的一个分块。
+///
+/// 同一文件的多个分块可以并发上传，每个分块通过 `Content-Range` 指明其在文件中的位置，
+/// 分块之间不能重叠，所有分块覆盖整个文件后将暂存文件移动到下载目录。
+#[handler]
+pub(super) async fn upload_chunk(req: &mut Request, depot: &Depot) -> ServerResult<()> {
+    let session = session::current(depot)?;
+    let target = query_target(req)?;
+
+    let (first, last, size) = match req
+        .header::<String>("content-range")
+        .as_deref()
+        .and_then(parse_content_range)
+    {
+        Some(r) => r,
+        None => {
+            error!("请求头中未找到有效的 Content-Range");
+            return Err(ServerError::new("分块范围无效", "请通过小路互传扫码访问"));
+        }
+    };
+    let len = last - first + 1;
+    let end = last + 1;
+
+    debug!(
+        message = "收到分块",
+        name = target.name,
+        folders = ?target.folders,
+        first = first,
+        last = last,
+        size = size,
+        ip = ?req.remote_addr()
+    );
+
+    let dir = target.dir().await;
+    let policy = *CONFLICT_POLICY.read().await;
+
+    let staging = part_path(&dir, &target.name, size, session, true);
+
+    if CANCELLED_CHUNKED_UPLOADS
+        .lock()
+        .unwrap()
+        .contains_key(&staging)
+    {
+        debug!(
+            message = "文件已被取消，拒绝分块",
+            name = target.name,
+            first = first
+        );
+        return Err(cancelled_error());
+    }
+
+    // 暂存文件创建时已设为完整大小，只在第一个分块时检查
+    if !CHUNKED_UPLOADS.lock().await.contains_key(&staging) {
+        quota::check(session, size, size).await?;
+    }
+    approval::request(req, session, &staging, &target.name, target.folder(), size).await?;
+
+    create_dir(&dir).await?;
+    if !target.folders.is_empty() {
+        track_part_dir(&dir).await;
+    }
+
+    let (info, control, start, received, file_progress, bucket) = {
+        let mut uploads = CHUNKED_UPLOADS.lock().await;
+
+        // 等待确认期间文件可能已被取消
+        if CANCELLED_CHUNKED_UPLOADS
+            .lock()
+            .unwrap()
+            .contains_key(&staging)
+        {
+            return Err(cancelled_error());
+        }
+
+        if !uploads.contains_key(&staging) {
+            let file = File::create(&staging).await.map_err(|e| {
+                error!(message = "新建暂存文件时出错", path = ?staging, error = ?e);
+                ServerError::Internal
+            })?;
+            file.set_len(size).await.map_err(|e| {
+                error!(message = "设置暂存文件大小时出错", path = ?staging, error = ?e);
+                ServerError::Internal
+            })?;
+
+            info!(message = "开始分块上传", name = target.name, size = size, ip = ?req.remote_addr());
+
+            let start = Instant::now();
+            let transfer = Transfer::register();
+            // 接收过程中按预测的保存路径显示进度
+            let info = TaskInfo::new(
+                transfer.id(),
+                preview_destination(&dir, &target.name, policy)?,
+                &target,
+                size,
+                session::client_ip(req),
+                start,
+            );
+
+            uploads.insert(
+                staging.clone(),
+                ChunkedUpload::new(session, transfer, info, start),
+            );
+        }
+
+        let upload = uploads.get_mut(&staging).unwrap();
+        let control = upload.transfer.control();
+
+        if control.is_cancelled() {
+            let upload = uploads.remove(&staging).unwrap();
+            CANCELLED_CHUNKED_UPLOADS
+                .lock()
+                .unwrap()
+                .insert(staging.clone(), session);
+            drop(uploads);
+
+            let received = upload.received.load(Ordering::Relaxed);
+            return Err(discard_cancelled(&staging, &upload.info, received).await);
+        }
+
+        if upload.completed.get(&first) == Some(&end) {
+            debug!(
+                message = "分块已接收，忽略重复的分块",
+                name = target.name,
+                first = first
+            );
+            return Ok(());
+        }
+
+        if overlaps(&upload.completed, first, end) || overlaps(&upload.receiving, first, end) {
+            error!(
+                message = "分块与已接收或正在接收的分块重叠",
+                name = target.name,
+                first = first,
+                last = last
+            );
+            return Err(ServerError::with_status(
+                StatusCode::LOCKED,
+                "分块范围重叠",
+                "请等待正在上传的分块结束后再试",
+            ));
+        }
+
+        upload.receiving.insert(first, end);
+
+        (
+            upload.info.clone(),
+            control,
+            upload.start,
+            upload.received.clone(),
+            upload.progress.clone(),
+            upload.bucket.clone(),
+        )
+    };
+
+    // 当前分块已计入总进度的字节数，分块失败时需要从总进度中减去
+    let chunk_received = Arc::new(AtomicU64::new(0));
+
+    let body = req.take_body();
+    let stream = ReadProgressStream::new(
+        body,
+        Box::new({
+            let info = info.clone();
+            let received = received.clone();
+            let chunk_received = chunk_received.clone();
+            move |progress: Progress| {
+                let progress = progress.bytes;
+                let delta = progress - chunk_received.swap(progress, Ordering::Relaxed);
+                received.fetch_add(delta, Ordering::Relaxed);
+
+                // 在锁内读取总数，保证通知的进度不会倒退
+                let now = Instant::now();
+                let mut file_progress = file_progress.lock().unwrap();
+                let total = received.load(Ordering::Relaxed);
+
+                if let Some(speed) = file_progress.update(now, total, size) {
+                    let average_speed = mb_per_sec(total, now.duration_since(start));
+                    info.emit_progress(total, speed, average_speed);
+                }
+            }
+        }),
+    )
+    .with_bucket(bucket);
+
+    let mut stream_reader = StreamReader::new(stream);
+    let copied = match OpenOptions::new().write(true).open(&staging).await {
+        Ok(mut file) => {
+            let copied = match file.seek(SeekFrom::Start(first)).await {
+                Ok(_) => control.copy(&mut stream_reader, &mut file).await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = file.flush().await {
+                error!(message = "写入暂存文件时出错", path = ?staging, error = ?e);
+            }
+            copied
+        }
+        Err(e) => {
+            error!(message = "打开暂存文件时出错", path = ?staging, error = ?e);
+            Err(e)
+        }
+    };
+
+    let mut uploads = CHUNKED_UPLOADS.lock().await;
+
+    if control.is_cancelled() {
+        // 其他分块可能已经清理了此文件
+        if let Some(upload) = uploads.remove(&staging) {
+            CANCELLED_CHUNKED_UPLOADS
+                .lock()
+                .unwrap()
+                .insert(staging.clone(), session);
+            drop(uploads);
+
+            let received = upload.received.load(Ordering::Relaxed);
+            return Err(discard_cancelled(&staging, &info, received).await);
+        }
+        return Err(cancelled_error());
+    }
+
+    let upload = match uploads.get_mut(&staging) {
+        Some(u) => u,
+        // 会话已过期，上传已被删除
+        None => {
+            return Err(ServerError::with_status(
+                StatusCode::GONE,
+                "上传已过期",
+                "请重新上传此文件",
+            ))
+        }
+    };
+    upload.receiving.remove(&first);
+
+    match copied {
+        Ok(n) if n == len => {}
+        r => {
+            error!(message = "分块接收失败", name = target.name, first = first, result = ?r);
+            received.fetch_sub(chunk_received.load(Ordering::Relaxed), Ordering::Relaxed);
+
+            return Err(ServerError::new("分块接收失败", "请重新上传此分块"));
+        }
+    }
+
+    upload.completed.insert(first, end);
+
+    if let Some(hash) = expected_hash(req) {
+        upload.expected_hash.get_or_insert(hash);
+    }
+
+    if upload.completed_len() < size {
+        return Ok(());
+    }
+
+    let expected_hash = upload.expected_hash.take();
+    // 离开作用域时注销传输
+    let _upload = uploads.remove(&staging);
+    drop(uploads);
+
+    // 分块乱序写入，只能在全部接收后读取整个文件计算哈希
+    let hash = match expected_hash {
+        Some(expected) => {
+            let hasher = hash_file(&staging, size).await?;
+            Some(verify_hash(&staging, &info, &expected, hasher, size).await?)
+        }
+        None => None,
+    };
+
+    let saved = persist(&staging, &dir, &target.name, policy).await?;
+
+    let cost = start.elapsed();
+
+    let speed = mb_per_sec(size, cost);
+    emit_saved(&info, &saved, speed, hash.as_deref());
+    session::record_transfer(session, 1, size).await;
+    info.record
+        .finish(Outcome::Completed, Some(&saved), hash.as_deref(), size)
+        .await;
+
+    info!(
+        message = "已保存文件",
+        path = ?saved,
+        size = size,
+        hash = ?hash,
+        ip = ?req.remote_addr(),
+        cost = ?cost,
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_valid_content_range() {
+        assert_eq!(parse_content_range("bytes 0-0/1"), Some((0, 0, 1)));
+        assert_eq!(parse_content_range("bytes 0-9/10"), Some((0, 9, 10)));
+        assert_eq!(parse_content_range("bytes 5-9/100"), Some((5, 9, 100)));
+        assert_eq!(
+            parse_content_range("bytes 0-18446744073709551614/18446744073709551615"),
+            Some((0, u64::MAX - 1, u64::MAX))
+        );
+    }
+
+    #[test]
+    fn reject_invalid_content_range() {
+        // 结束位置超出文件大小
+        assert_eq!(parse_content_range("bytes 0-10/10"), None);
+        // 起始位置大于结束位置
+        assert_eq!(parse_content_range("bytes 5-4/10"), None);
+        // 空文件无法表示任何范围
+        assert_eq!(parse_content_range("bytes 0-0/0"), None);
+        assert_eq!(parse_content_range("bytes */10"), None);
+        assert_eq!(parse_content_range("bytes 0-9/*"), None);
+        assert_eq!(parse_content_range("bytes -1-9/10"), None);
+        assert_eq!(parse_content_range("0-9/10"), None);
+        assert_eq!(parse_content_range("bytes 0-9"), None);
+        assert_eq!(parse_content_range(""), None);
+        assert_eq!(parse_content_range("bytes 0-18446744073709551616/10"), None);
+    }
+
+    #[test]
+    fn coalesce_chunk_progress() {
+        let start = Instant::now();
+        let interval = progress_interval();
+        let mut progress = ChunkProgress::new(start);
+
+        assert!(progress.update(start, 10, 100).is_some());
+
+        // 其他分块在间隔内的进度被合并
+        assert!(progress.update(start + interval / 2, 20, 100).is_none());
+        assert!(progress.update(start + interval / 2, 30, 100).is_none());
+        assert!(progress.update(start + interval, 40, 100).is_some());
+
+        // 接收完毕时立即通知，且只通知一次
+        let now = start + interval + interval / 2;
+        assert!(progress.update(now, 100, 100).is_some());
+        assert!(progress.update(now, 100, 100).is_none());
+    }
+
+    #[test]
+    fn detect_overlapping_chunks() {
+        let ranges = BTreeMap::from([(0, 10), (20, 30)]);
+
+        assert!(overlaps(&ranges, 5, 15));
+        assert!(overlaps(&ranges, 9, 10));
+        assert!(overlaps(&ranges, 15, 25));
+        assert!(overlaps(&ranges, 0, 30));
+        assert!(overlaps(&ranges, 25, 26));
+
+        assert!(!overlaps(&ranges, 10, 20));
+        assert!(!overlaps(&ranges, 30, 40));
+        assert!(!overlaps(&BTreeMap::new(), 0, 10));
+    }
+}
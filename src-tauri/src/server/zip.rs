@@ -0,0 +1,602 @@
+//! 不压缩（store）的流式 ZIP 写入。
+//!
+//! 发送的文件大多是照片、视频等已压缩过的文件，再压缩几乎没有收益，所以只存储原始数据。
+//! 每个文件的 CRC32 在写入数据时计算，写在数据之后的 data descriptor 中，无需临时文件。
+//! 文件大小在开始前就已确定，因此可以提前算出整个压缩包的大小。
+
+use std::collections::HashSet;
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use time::OffsetDateTime;
+use tokio::fs::{self, File};
+use tokio::io::{AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+const LOCAL_HEADER_SIGNATURE: u32 = 0x04034b50;
+const DATA_DESCRIPTOR_SIGNATURE: u32 = 0x08074b50;
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x02014b50;
+const ZIP64_END_SIGNATURE: u32 = 0x06064b50;
+const ZIP64_LOCATOR_SIGNATURE: u32 = 0x07064b50;
+const END_SIGNATURE: u32 = 0x06054b50;
+
+const ZIP64_EXTRA_ID: u16 = 0x0001;
+
+/// 使用 data descriptor，文件名为 UTF-8
+const FLAGS: u16 = 0x0008 | 0x0800;
+
+const VERSION: u16 = 20;
+const VERSION_ZIP64: u16 = 45;
+
+const MAX_U16: u64 = u16::MAX as u64;
+const MAX_U32: u64 = u32::MAX as u64;
+
+struct Entry {
+    path: PathBuf,
+    name: String,
+    size: u64,
+    /// DOS 格式的修改时间和日期
+    modified: (u16, u16),
+    /// 本地文件头在压缩包中的偏移
+    offset: u64,
+}
+
+impl Entry {
+    fn zip64(&self) -> bool {
+        self.size >= MAX_U32
+    }
+
+    fn version(&self) -> u16 {
+        if self.zip64() || self.offset >= MAX_U32 {
+            VERSION_ZIP64
+        } else {
+            VERSION
+        }
+    }
+
+    fn local_header(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(30 + self.name.len() + 20);
+
+        put_u32(&mut buf, LOCAL_HEADER_SIGNATURE);
+        put_u16(&mut buf, self.version());
+        put_u16(&mut buf, FLAGS);
+        put_u16(&mut buf, 0); // store
+        put_u16(&mut buf, self.modified.0);
+        put_u16(&mut buf, self.modified.1);
+        put_u32(&mut buf, 0); // crc32 写在 data descriptor 中
+
+        if self.zip64() {
+            put_u32(&mut buf, u32::MAX);
+            put_u32(&mut buf, u32::MAX);
+        } else {
+            put_u32(&mut buf, 0);
+            put_u32(&mut buf, 0);
+        }
+
+        put_u16(&mut buf, self.name.len() as u16);
+        put_u16(&mut buf, if self.zip64() { 20 } else { 0 });
+        buf.extend_from_slice(self.name.as_bytes());
+
+        if self.zip64() {
+            put_u16(&mut buf, ZIP64_EXTRA_ID);
+            put_u16(&mut buf, 16);
+            put_u64(&mut buf, 0);
+            put_u64(&mut buf, 0);
+        }
+
+        buf
+    }
+
+    fn data_descriptor(&self, crc: u32) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(24);
+
+        put_u32(&mut buf, DATA_DESCRIPTOR_SIGNATURE);
+        put_u32(&mut buf, crc);
+
+        if self.zip64() {
+            put_u64(&mut buf, self.size);
+            put_u64(&mut buf, self.size);
+        } else {
+            put_u32(&mut buf, self.size as u32);
+            put_u32(&mut buf, self.size as u32);
+        }
+
+        buf
+    }
+
+    fn central_header(&self, crc: u32) -> Vec<u8> {
+        let offset_zip64 = self.offset >= MAX_U32;
+
+        let mut extra = Vec::new();
+        if self.zip64() {
+            put_u64(&mut extra, self.size);
+            put_u64(&mut extra, self.size);
+        }
+        if offset_zip64 {
+            put_u64(&mut extra, self.offset);
+        }
+
+        let mut buf = Vec::with_capacity(46 + self.name.len() + 4 + extra.len());
+
+        put_u32(&mut buf, CENTRAL_HEADER_SIGNATURE);
+        put_u16(&mut buf, VERSION_ZIP64); // version made by
+        put_u16(&mut buf, self.version());
+        put_u16(&mut buf, FLAGS);
+        put_u16(&mut buf, 0); // store
+        put_u16(&mut buf, self.modified.0);
+        put_u16(&mut buf, self.modified.1);
+        put_u32(&mut buf, crc);
+
+        if self.zip64() {
+            put_u32(&mut buf, u32::MAX);
+            put_u32(&mut buf, u32::MAX);
+        } else {
+            put_u32(&mut buf, self.size as u32);
+            put_u32(&mut buf, self.size as u32);
+        }
+
+        put_u16(&mut buf, self.name.len() as u16);
+        put_u16(
+            &mut buf,
+            if extra.is_empty() {
+                0
+            } else {
+                4 + extra.len() as u16
+            },
+        );
+        put_u16(&mut buf, 0); // comment
+        put_u16(&mut buf, 0); // disk number
+        put_u16(&mut buf, 0); // internal attributes
+        put_u32(&mut buf, 0); // external attributes
+        put_u32(
+            &mut buf,
+            if offset_zip64 {
+                u32::MAX
+            } else {
+                self.offset as u32
+            },
+        );
+        buf.extend_from_slice(self.name.as_bytes());
+
+        if !extra.is_empty() {
+            put_u16(&mut buf, ZIP64_EXTRA_ID);
+            put_u16(&mut buf, extra.len() as u16);
+            buf.extend_from_slice(&extra);
+        }
+
+        buf
+    }
+
+    /// 本地文件头、数据和 data descriptor 的总长度
+    fn len(&self) -> u64 {
+        self.local_header().len() as u64 + self.size + self.data_descriptor(0).len() as u64
+    }
+}
+
+/// 可以流式写入的 ZIP 压缩包
+pub(super) struct ZipArchive {
+    entries: Vec<Entry>,
+    central_offset: u64,
+    central_len: u64,
+}
+
+impl ZipArchive {
+    /// 由文件路径和其在压缩包中的路径创建压缩包，压缩包中的路径使用 `/` 分隔。
+    pub(super) async fn new<I>(files: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = (PathBuf, String)>,
+    {
+        let mut entries = Vec::new();
+        let mut names = HashSet::new();
+        let mut offset = 0;
+
+        for (path, name) in files {
+            let metadata = fs::metadata(&path).await?;
+            let name = unique_name(&mut names, name);
+
+            if name.len() > MAX_U16 as usize {
+                return Err(Error::new(ErrorKind::InvalidInput, "文件路径过长"));
+            }
+
+            let entry = Entry {
+                path,
+                name,
+                size: metadata.len(),
+                modified: dos_datetime(metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH)),
+                offset,
+            };
+
+            offset += entry.len();
+            entries.push(entry);
+        }
+
+        let central_len = entries
+            .iter()
+            .map(|e| e.central_header(0).len() as u64)
+            .sum();
+
+        Ok(Self {
+            entries,
+            central_offset: offset,
+            central_len,
+        })
+    }
+
+    fn zip64(&self) -> bool {
+        self.entries.len() as u64 >= MAX_U16
+            || self.central_offset >= MAX_U32
+            || self.central_len >= MAX_U32
+    }
+
+    fn end_records(&self) -> Vec<u8> {
+        let count = self.entries.len() as u64;
+        let mut buf = Vec::with_capacity(56 + 20 + 22);
+
+        if self.zip64() {
+            let zip64_end_offset = self.central_offset + self.central_len;
+
+            put_u32(&mut buf, ZIP64_END_SIGNATURE);
+            put_u64(&mut buf, 44); // 此记录剩余部分的长度
+            put_u16(&mut buf, VERSION_ZIP64);
+            put_u16(&mut buf, VERSION_ZIP64);
+            put_u32(&mut buf, 0);
+            put_u32(&mut buf, 0);
+            put_u64(&mut buf, count);
+            put_u64(&mut buf, count);
+            put_u64(&mut buf, self.central_len);
+            put_u64(&mut buf, self.central_offset);
+
+            put_u32(&mut buf, ZIP64_LOCATOR_SIGNATURE);
+            put_u32(&mut buf, 0);
+            put_u64(&mut buf, zip64_end_offset);
+            put_u32(&mut buf, 1);
+        }
+
+        put_u32(&mut buf, END_SIGNATURE);
+        put_u16(&mut buf, 0);
+        put_u16(&mut buf, 0);
+        put_u16(&mut buf, count.min(MAX_U16) as u16);
+        put_u16(&mut buf, count.min(MAX_U16) as u16);
+        put_u32(&mut buf, self.central_len.min(MAX_U32) as u32);
+        put_u32(&mut buf, self.central_offset.min(MAX_U32) as u32);
+        put_u16(&mut buf, 0); // comment
+
+        buf
+    }
+
+    /// 整个压缩包的字节数
+    pub(super) fn len(&self) -> u64 {
+        self.central_offset + self.central_len + self.end_records().len() as u64
+    }
+
+    /// 依次读取所有文件并写入压缩包。
+    ///
+    /// 文件大小与创建压缩包时不一致时返回错误，以保证写入的字节数与 [`ZipArchive::len`] 一致。
+    pub(super) async fn write_to<W: AsyncWrite + Unpin>(&self, writer: &mut W) -> Result<()> {
+        let mut crcs = Vec::with_capacity(self.entries.len());
+        let mut buf = vec![0; 64 * 1024];
+
+        for entry in &self.entries {
+            writer.write_all(&entry.local_header()).await?;
+
+            let crc = copy_file(&entry.path, entry.size, writer, &mut buf).await?;
+
+            writer.write_all(&entry.data_descriptor(crc)).await?;
+            crcs.push(crc);
+        }
+
+        for (entry, crc) in self.entries.iter().zip(crcs) {
+            writer.write_all(&entry.central_header(crc)).await?;
+        }
+
+        writer.write_all(&self.end_records()).await?;
+        writer.flush().await
+    }
+}
+
+/// 复制文件数据并计算 CRC32。
+async fn copy_file<W: AsyncWrite + Unpin>(
+    path: &Path,
+    size: u64,
+    writer: &mut W,
+    buf: &mut [u8],
+) -> Result<u32> {
+    let mut file = File::open(path).await?;
+    let mut hasher = crc32fast::Hasher::new();
+    let mut copied = 0;
+
+    loop {
+        let n = file.read(buf).await?;
+        if n == 0 {
+            break;
+        }
+
+        copied += n as u64;
+        if copied > size {
+            break;
+        }
+
+        hasher.update(&buf[..n]);
+        writer.write_all(&buf[..n]).await?;
+    }
+
+    if copied != size {
+        return Err(Error::new(
+            ErrorKind::UnexpectedEof,
+            format!("文件大小已改变: {}", path.display()),
+        ));
+    }
+
+    Ok(hasher.finalize())
+}
+
+/// 压缩包中有同名文件时，在文件名后添加 ` (n)`。
+fn unique_name(names: &mut HashSet<String>, name: String) -> String {
+    if names.insert(name.clone()) {
+        return name;
+    }
+
+    let (stem, extension) = match name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() && !stem.ends_with('/') => {
+            (stem.to_owned(), format!(".{}", ext))
+        }
+        _ => (name.clone(), String::new()),
+    };
+
+    let mut n = 1;
+    loop {
+        let candidate = format!("{} ({}){}", stem, n, extension);
+        if names.insert(candidate.clone()) {
+            return candidate;
+        }
+        n += 1;
+    }
+}
+
+/// 转换为 DOS 格式的时间和日期，早于 1980 年时使用 1980-01-01。
+fn dos_datetime(time: SystemTime) -> (u16, u16) {
+    let time = OffsetDateTime::from(time);
+
+    if time.year() < 1980 {
+        return (0, (1 << 5) | 1);
+    }
+
+    let dos_time =
+        ((time.hour() as u16) << 11) | ((time.minute() as u16) << 5) | (time.second() as u16 / 2);
+    let dos_date = (((time.year() - 1980).min(127) as u16) << 9)
+        | ((time.month() as u16) << 5)
+        | time.day() as u16;
+
+    (dos_time, dos_date)
+}
+
+fn put_u16(buf: &mut Vec<u8>, n: u16) {
+    buf.extend_from_slice(&n.to_le_bytes());
+}
+
+fn put_u32(buf: &mut Vec<u8>, n: u32) {
+    buf.extend_from_slice(&n.to_le_bytes());
+}
+
+fn put_u64(buf: &mut Vec<u8>, n: u64) {
+    buf.extend_from_slice(&n.to_le_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Read};
+
+    use super::*;
+
+    async fn write_files(dir: &Path, files: &[(&str, &[u8])]) -> Vec<(PathBuf, String)> {
+        let mut paths = Vec::new();
+
+        for (i, (name, content)) in files.iter().enumerate() {
+            let path = dir.join(i.to_string());
+            fs::write(&path, content).await.unwrap();
+            paths.push((path, name.to_string()));
+        }
+
+        paths
+    }
+
+    fn read_u16(buf: &[u8], at: usize) -> u16 {
+        u16::from_le_bytes(buf[at..at + 2].try_into().unwrap())
+    }
+
+    fn read_u32(buf: &[u8], at: usize) -> u32 {
+        u32::from_le_bytes(buf[at..at + 4].try_into().unwrap())
+    }
+
+    fn read_u64(buf: &[u8], at: usize) -> u64 {
+        u64::from_le_bytes(buf[at..at + 8].try_into().unwrap())
+    }
+
+    /// 不读取文件，只用于检查头部，大小和偏移可以任意指定
+    fn entry(name: &str, size: u64, offset: u64) -> Entry {
+        Entry {
+            path: PathBuf::new(),
+            name: name.to_string(),
+            size,
+            modified: (0, 0),
+            offset,
+        }
+    }
+
+    async fn archive_bytes(archive: &ZipArchive) -> Vec<u8> {
+        let mut buf = Vec::new();
+        archive.write_to(&mut buf).await.unwrap();
+        buf
+    }
+
+    #[tokio::test]
+    async fn len_matches_written_bytes() {
+        let dir = tempfile::tempdir().unwrap();
+        let large = vec![7; 200 * 1024];
+        let files = write_files(
+            dir.path(),
+            &[
+                ("a.txt", b"hello"),
+                ("empty", b""),
+                ("dir/large.bin", &large),
+            ],
+        )
+        .await;
+
+        let archive = ZipArchive::new(files).await.unwrap();
+        let buf = archive_bytes(&archive).await;
+
+        assert_eq!(archive.len(), buf.len() as u64);
+    }
+
+    #[tokio::test]
+    async fn empty_archive() {
+        let archive = ZipArchive::new(Vec::new()).await.unwrap();
+        let buf = archive_bytes(&archive).await;
+
+        assert_eq!(archive.len(), buf.len() as u64);
+        assert_eq!(zip::ZipArchive::new(Cursor::new(buf)).unwrap().len(), 0);
+    }
+
+    #[tokio::test]
+    async fn round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let files: &[(&str, &[u8])] = &[
+            ("a.txt", b"hello"),
+            ("文件夹/b.txt", b"world"),
+            ("a.txt", b"duplicate"),
+            ("empty", b""),
+        ];
+        let paths = write_files(dir.path(), files).await;
+
+        let archive = ZipArchive::new(paths).await.unwrap();
+        let mut reader = zip::ZipArchive::new(Cursor::new(archive_bytes(&archive).await)).unwrap();
+
+        let expected: &[(&str, &[u8])] = &[
+            ("a.txt", b"hello"),
+            ("文件夹/b.txt", b"world"),
+            ("a (1).txt", b"duplicate"),
+            ("empty", b""),
+        ];
+        assert_eq!(reader.len(), expected.len());
+
+        for (i, (name, content)) in expected.iter().enumerate() {
+            let mut file = reader.by_index(i).unwrap();
+            assert_eq!(file.name(), *name);
+            assert_eq!(file.compression(), zip::CompressionMethod::Stored);
+
+            // 读取到末尾时校验 CRC32
+            let mut data = Vec::new();
+            file.read_to_end(&mut data).unwrap();
+            assert_eq!(data, *content);
+        }
+    }
+
+    #[tokio::test]
+    async fn reject_changed_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let files = write_files(dir.path(), &[("a.txt", b"hello")]).await;
+        let archive = ZipArchive::new(files.clone()).await.unwrap();
+
+        fs::write(&files[0].0, b"hello world").await.unwrap();
+
+        let err = archive.write_to(&mut Vec::new()).await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn zip64_size_headers() {
+        let entry = entry("big.bin", MAX_U32 + 1, 0);
+
+        let local = entry.local_header();
+        assert_eq!(local.len(), 30 + 7 + 20);
+        assert_eq!(read_u32(&local, 0), LOCAL_HEADER_SIGNATURE);
+        assert_eq!(read_u16(&local, 4), VERSION_ZIP64);
+        assert_eq!(read_u32(&local, 18), u32::MAX);
+        assert_eq!(read_u32(&local, 22), u32::MAX);
+        assert_eq!(read_u16(&local, 28), 20);
+        assert_eq!(read_u16(&local, 37), ZIP64_EXTRA_ID);
+        assert_eq!(read_u16(&local, 39), 16);
+
+        let descriptor = entry.data_descriptor(0x12345678);
+        assert_eq!(descriptor.len(), 24);
+        assert_eq!(read_u64(&descriptor, 8), MAX_U32 + 1);
+        assert_eq!(read_u64(&descriptor, 16), MAX_U32 + 1);
+        assert_eq!(entry.len(), 57 + MAX_U32 + 1 + 24);
+
+        let central = entry.central_header(0x12345678);
+        assert_eq!(central.len(), 46 + 7 + 4 + 16);
+        assert_eq!(read_u16(&central, 6), VERSION_ZIP64);
+        assert_eq!(read_u32(&central, 16), 0x12345678);
+        assert_eq!(read_u32(&central, 20), u32::MAX);
+        assert_eq!(read_u32(&central, 24), u32::MAX);
+        assert_eq!(read_u16(&central, 30), 4 + 16);
+        assert_eq!(read_u32(&central, 42), 0);
+        assert_eq!(read_u16(&central, 53), ZIP64_EXTRA_ID);
+        assert_eq!(read_u16(&central, 55), 16);
+        assert_eq!(read_u64(&central, 57), MAX_U32 + 1);
+        assert_eq!(read_u64(&central, 65), MAX_U32 + 1);
+    }
+
+    #[test]
+    fn zip64_offset_headers() {
+        // 文件本身不大，但位于 4 GiB 之后
+        let entry = entry("a.txt", 5, MAX_U32 + 10);
+
+        let local = entry.local_header();
+        assert_eq!(local.len(), 30 + 5);
+        assert_eq!(read_u16(&local, 4), VERSION_ZIP64);
+        assert_eq!(read_u16(&local, 28), 0);
+        assert_eq!(entry.data_descriptor(0).len(), 16);
+
+        let central = entry.central_header(0);
+        assert_eq!(central.len(), 46 + 5 + 4 + 8);
+        assert_eq!(read_u32(&central, 20), 5);
+        assert_eq!(read_u32(&central, 24), 5);
+        assert_eq!(read_u16(&central, 30), 4 + 8);
+        assert_eq!(read_u32(&central, 42), u32::MAX);
+        assert_eq!(read_u16(&central, 51), ZIP64_EXTRA_ID);
+        assert_eq!(read_u16(&central, 53), 8);
+        assert_eq!(read_u64(&central, 55), MAX_U32 + 10);
+    }
+
+    #[test]
+    fn zip64_end_records() {
+        let big = entry("big.bin", MAX_U32 + 1, 0);
+        let small = entry("a.txt", 5, big.len());
+        let central_offset = big.len() + small.len();
+        let central_len = (big.central_header(0).len() + small.central_header(0).len()) as u64;
+
+        let archive = ZipArchive {
+            entries: vec![big, small],
+            central_offset,
+            central_len,
+        };
+        assert!(archive.zip64());
+
+        let end = archive.end_records();
+        assert_eq!(end.len(), 56 + 20 + 22);
+
+        assert_eq!(read_u32(&end, 0), ZIP64_END_SIGNATURE);
+        assert_eq!(read_u64(&end, 4), 44);
+        assert_eq!(read_u64(&end, 24), 2);
+        assert_eq!(read_u64(&end, 32), 2);
+        assert_eq!(read_u64(&end, 40), central_len);
+        assert_eq!(read_u64(&end, 48), central_offset);
+
+        assert_eq!(read_u32(&end, 56), ZIP64_LOCATOR_SIGNATURE);
+        assert_eq!(read_u64(&end, 64), central_offset + central_len);
+        assert_eq!(read_u32(&end, 72), 1);
+
+        assert_eq!(read_u32(&end, 76), END_SIGNATURE);
+        assert_eq!(read_u16(&end, 84), 2);
+        assert_eq!(read_u16(&end, 86), 2);
+        assert_eq!(read_u32(&end, 88), central_len as u32);
+        assert_eq!(read_u32(&end, 92), u32::MAX);
+
+        assert_eq!(
+            archive.len(),
+            central_offset + central_len + end.len() as u64
+        );
+    }
+}
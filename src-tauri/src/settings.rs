@@ -0,0 +1,308 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::fs::{self, File};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::RwLock;
+
+use crate::error::{FluxyError, FluxyResult};
+use crate::i18n::Locale;
+use crate::lazy::APP_CONFIG_DIR;
+use crate::server::quota::DEFAULT_DISK_SPACE_MARGIN;
+use crate::server::session::{DEFAULT_IDLE_TIMEOUT, DEFAULT_QR_CODE_TTL};
+use crate::server::{ConflictPolicy, DEFAULT_PORT};
+use crate::stream::DEFAULT_PROGRESS_INTERVAL;
+
+const SETTINGS_FILE: &str = "settings.json";
+
+/// 当前配置文件的版本，修改配置结构时递增并在 [`migrate`] 中添加迁移步骤
+const SETTINGS_VERSION: u64 = 2;
+
+lazy_static! {
+    pub(crate) static ref SETTINGS: RwLock<Settings> = RwLock::new(Settings::default());
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct Settings {
+    version: u64,
+    pub(crate) downloads_dir: PathBuf,
+    /// 首选监听端口，修改后重启生效
+    pub(crate) port: u16,
+    /// 使用自签名证书提供 HTTPS，修改后重启生效
+    pub(crate) tls: bool,
+    pub(crate) conflict_policy: ConflictPolicy,
+    /// 界面语言，为空时跟随系统
+    pub(crate) locale: Option<Locale>,
+    /// 二维码地址使用的网卡，为空时自动选择
+    pub(crate) preferred_interface: Option<String>,
+    /// 未使用的二维码的有效期（秒）
+    pub(crate) qr_code_ttl: u64,
+    /// 已连接的会话没有请求后的有效期（秒）
+    pub(crate) idle_timeout: u64,
+    /// 接收文件前需要在桌面端确认
+    pub(crate) require_upload_approval: bool,
+    /// 接收文件后下载目录所在磁盘至少保留的空间（字节）
+    pub(crate) disk_space_margin: u64,
+    /// 单个文件的最大字节数，为空时不限制
+    pub(crate) max_file_size: Option<u64>,
+    /// 一个会话最多接收的字节数，为空时不限制
+    pub(crate) max_session_size: Option<u64>,
+    /// 传输进度通知的最小间隔（毫秒）
+    pub(crate) progress_interval: u64,
+    /// 所有传输共用的限速（字节/秒），为空时不限制
+    pub(crate) bandwidth_limit: Option<u64>,
+    /// 每个传输单独的限速（字节/秒），为空时不限制
+    pub(crate) transfer_bandwidth_limit: Option<u64>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            version: SETTINGS_VERSION,
+            downloads_dir: dirs::download_dir().unwrap().join("alley"),
+            port: DEFAULT_PORT,
+            tls: false,
+            conflict_policy: ConflictPolicy::default(),
+            locale: None,
+            preferred_interface: None,
+            qr_code_ttl: DEFAULT_QR_CODE_TTL,
+            idle_timeout: DEFAULT_IDLE_TIMEOUT,
+            require_upload_approval: false,
+            disk_space_margin: DEFAULT_DISK_SPACE_MARGIN,
+            max_file_size: None,
+            max_session_size: None,
+            progress_interval: DEFAULT_PROGRESS_INTERVAL,
+            bandwidth_limit: None,
+            transfer_bandwidth_limit: None,
+        }
+    }
+}
+
+impl Settings {
+    /// 检查前端传入的配置，值为 0 的间隔会让会话立即过期或进度通知失去节流
+    pub(crate) fn validate(&self) -> FluxyResult<()> {
+        let invalid = if self.downloads_dir.as_os_str().is_empty() {
+            Some("downloads_dir")
+        } else if self.qr_code_ttl == 0 {
+            Some("qr_code_ttl")
+        } else if self.idle_timeout == 0 {
+            Some("idle_timeout")
+        } else if self.progress_interval == 0 {
+            Some("progress_interval")
+        } else {
+            None
+        };
+
+        match invalid {
+            Some(field) => {
+                warn!(message = "配置无效", field = field);
+                Err(FluxyError::InvalidSettings(field))
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+fn settings_path() -> PathBuf {
+    APP_CONFIG_DIR.join(SETTINGS_FILE)
+}
+
+/// 版本 1 中大小和速度限制为 0 时同样表示不限制，版本 2 统一使用 `null`。
+fn migrate_v1(value: &mut Value) {
+    let Some(obj) = value.as_object_mut() else {
+        return;
+    };
+
+    for key in [
+        "max_file_size",
+        "max_session_size",
+        "bandwidth_limit",
+        "transfer_bandwidth_limit",
+    ] {
+        if let Some(limit) = obj.get_mut(key) {
+            if limit.as_u64() == Some(0) {
+                *limit = Value::Null;
+            }
+        }
+    }
+}
+
+/// 将旧版本的配置逐版本迁移到当前版本。
+///
+/// 没有 `version` 字段的配置视为版本 0。
+fn migrate(mut value: Value) -> Value {
+    let mut version = value.get("version").and_then(Value::as_u64).unwrap_or(0);
+
+    if version > SETTINGS_VERSION {
+        warn!(
+            message = "配置文件版本高于当前程序支持的版本，未知字段将被忽略",
+            version = version
+        );
+        return value;
+    }
+
+    while version < SETTINGS_VERSION {
+        match version {
+            // 版本 0 与版本 1 的字段相同，只需补充版本号
+            0 => {}
+            1 => migrate_v1(&mut value),
+            _ => unreachable!(),
+        }
+
+        version += 1;
+        debug!(message = "已迁移配置文件", version = version);
+    }
+
+    if let Some(obj) = value.as_object_mut() {
+        obj.insert("version".to_owned(), version.into());
+    }
+
+    value
+}
+
+/// 读取配置文件，文件不存在或无法解析时使用默认配置。
+///
+/// 保留配置文件中的版本号，版本高于当前程序时拒绝保存，避免覆盖新版本程序的配置。
+pub(crate) async fn load() -> Settings {
+    let path = settings_path();
+
+    let content = match fs::read(&path).await {
+        Ok(c) => c,
+        Err(e) => {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                error!(message = "读取配置文件失败", path = ?path, error = ?e);
+            }
+            return Settings::default();
+        }
+    };
+
+    let settings = serde_json::from_slice(&content)
+        .map(migrate)
+        .and_then(serde_json::from_value::<Settings>);
+
+    match settings {
+        Ok(s) => {
+            info!(message = "已读取配置文件", settings = ?s);
+            s
+        }
+        Err(e) => {
+            error!(message = "解析配置文件失败，使用默认配置", path = ?path, error = ?e);
+            Settings::default()
+        }
+    }
+}
+
+/// 保存配置，先写入临时文件再重命名，避免写入中断时损坏配置文件。
+///
+/// 配置由更新版本的程序创建时返回错误，不覆盖配置文件。
+pub(crate) async fn save(settings: &Settings) -> FluxyResult<()> {
+    if settings.version > SETTINGS_VERSION {
+        warn!(
+            message = "配置文件版本高于当前程序支持的版本，拒绝保存",
+            version = settings.version
+        );
+        return Err(FluxyError::NewerSettings(settings.version));
+    }
+
+    let path = settings_path();
+    let tmp_path = path.with_extension("json.tmp");
+
+    let content = serde_json::to_vec_pretty(settings)?;
+
+    let mut file = File::create(&tmp_path).await.map_err(|e| {
+        error!(message = "创建临时配置文件失败", path = ?tmp_path, error = ?e);
+        e
+    })?;
+    file.write_all(&content).await?;
+    file.sync_all().await?;
+    drop(file);
+
+    fs::rename(&tmp_path, &path).await.map_err(|e| {
+        error!(message = "保存配置文件失败", path = ?path, error = ?e);
+        e
+    })?;
+
+    debug!(message = "配置已保存", path = ?path);
+
+    Ok(())
+}
+
+/// 修改并保存配置，返回修改后的配置。
+pub(crate) async fn update<F: FnOnce(&mut Settings)>(f: F) -> FluxyResult<Settings> {
+    let mut settings = SETTINGS.write().await;
+
+    let mut new_settings = settings.clone();
+    f(&mut new_settings);
+    // 版本号只由配置文件决定，不能被前端传入的配置修改
+    new_settings.version = settings.version;
+
+    save(&new_settings).await?;
+    *settings = new_settings.clone();
+
+    Ok(new_settings)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn migrate_unversioned_settings() {
+        let value = migrate(json!({ "port": 8080, "bandwidth_limit": 0 }));
+
+        assert_eq!(value["version"], SETTINGS_VERSION);
+        assert_eq!(value["port"], 8080);
+        assert_eq!(value["bandwidth_limit"], Value::Null);
+    }
+
+    #[test]
+    fn migrate_zero_limits() {
+        let value = migrate(json!({
+            "version": 1,
+            "max_file_size": 0,
+            "max_session_size": 1024,
+            "bandwidth_limit": 0,
+            "transfer_bandwidth_limit": null,
+        }));
+
+        assert_eq!(value["version"], 2);
+        assert_eq!(value["max_file_size"], Value::Null);
+        assert_eq!(value["max_session_size"], 1024);
+        assert_eq!(value["bandwidth_limit"], Value::Null);
+        assert_eq!(value["transfer_bandwidth_limit"], Value::Null);
+    }
+
+    #[test]
+    fn validate_settings() {
+        assert!(Settings::default().validate().is_ok());
+
+        let cases: [(&str, fn(&mut Settings)); 4] = [
+            ("downloads_dir", |s| s.downloads_dir = PathBuf::new()),
+            ("qr_code_ttl", |s| s.qr_code_ttl = 0),
+            ("idle_timeout", |s| s.idle_timeout = 0),
+            ("progress_interval", |s| s.progress_interval = 0),
+        ];
+
+        for (field, f) in cases {
+            let mut settings = Settings::default();
+            f(&mut settings);
+
+            match settings.validate() {
+                Err(FluxyError::InvalidSettings(invalid)) => assert_eq!(invalid, field),
+                other => panic!("{field}: {other:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn keep_newer_version() {
+        let value = migrate(json!({ "version": SETTINGS_VERSION + 1, "max_file_size": 0 }));
+
+        assert_eq!(value["version"], SETTINGS_VERSION + 1);
+        assert_eq!(value["max_file_size"], 0);
+    }
+}
@@ -2,59 +2,257 @@ use bytes::Bytes;
 use futures::stream::Stream;
 use futures::task::{Context, Poll};
 use salvo::http::{Body, ReqBody};
+use salvo::BoxedError;
+use sha2::{Digest, Sha256};
 use std::cell::OnceCell;
-use std::io::Result;
+use std::collections::VecDeque;
+use std::io::{Error, Result};
 use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::{Duration, Instant};
 
-pub(super) type ProgressHandler = Box<dyn FnMut(Duration, u64) + Send + Sync + 'static>;
+use crate::throttle::{Throttle, TransferBucket};
 
-pub(super) struct ReadProgressStream {
-    inner: ReqBody,
+/// 默认的进度通知间隔（毫秒）
+pub(crate) const DEFAULT_PROGRESS_INTERVAL: u64 = 100;
+
+/// 进度变化达到总长度的此比例时，不等间隔结束也会通知
+const PROGRESS_STEP: f64 = 0.01;
+
+/// 计算速度的时间窗口
+const SPEED_WINDOW: Duration = Duration::from_secs(2);
+
+/// 两次进度通知之间的最小间隔（毫秒）
+static PROGRESS_INTERVAL: AtomicU64 = AtomicU64::new(DEFAULT_PROGRESS_INTERVAL);
+
+/// 设置进度通知的间隔，单位为毫秒，对之后创建的流生效。
+pub(crate) fn set_progress_interval(millis: u64) {
+    PROGRESS_INTERVAL.store(millis, Ordering::Relaxed);
+}
+
+/// 两次进度通知之间的最小间隔
+pub(super) fn progress_interval() -> Duration {
+    Duration::from_millis(PROGRESS_INTERVAL.load(Ordering::Relaxed))
+}
+
+/// 一次进度通知
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Progress {
+    /// 已读取的字节数
+    pub(super) bytes: u64,
+    /// 最近一段时间内的速度，MB/s
+    pub(super) speed: f64,
+    /// 从开始读取到现在的平均速度，MB/s
+    pub(super) average_speed: f64,
+}
+
+pub(super) type ProgressHandler = Box<dyn FnMut(Progress) + Send + Sync + 'static>;
+
+/// 流结束或被丢弃时调用，参数为耗时与已读取的字节数
+pub(super) type FinishHandler = Box<dyn FnOnce(Duration, u64) + Send + Sync + 'static>;
+
+/// 平均速度，MB/s
+pub(super) fn mb_per_sec(bytes: u64, cost: Duration) -> f64 {
+    if cost.is_zero() {
+        0.
+    } else {
+        bytes as f64 / (1024 * 1024) as f64 / cost.as_secs_f64()
+    }
+}
+
+/// 按时间窗口计算速度
+pub(super) struct SpeedWindow {
+    /// 窗口内的采样，时间与当时已读取的字节数
+    samples: VecDeque<(Instant, u64)>,
+}
+
+impl SpeedWindow {
+    pub(super) fn new() -> Self {
+        Self {
+            samples: VecDeque::new(),
+        }
+    }
+
+    /// 记录一次采样并返回窗口内的平均速度，MB/s
+    pub(super) fn update(&mut self, now: Instant, bytes: u64) -> f64 {
+        self.samples.push_back((now, bytes));
+
+        // 至少保留两个采样，间隔较长时使用最近两次采样之间的速度
+        while self.samples.len() > 2 && now.duration_since(self.samples[0].0) > SPEED_WINDOW {
+            self.samples.pop_front();
+        }
+
+        let (first_time, first_bytes) = self.samples[0];
+        mb_per_sec(
+            bytes.saturating_sub(first_bytes),
+            now.duration_since(first_time),
+        )
+    }
+}
+
+/// 读取请求体或响应体时报告进度。
+///
+/// 进度按间隔合并后通知，读取结束时总会通知最后一次进度。
+/// 读取速度受 [`crate::throttle::set_limits`] 设置的限速约束。
+pub(super) struct ReadProgressStream<B = ReqBody> {
+    inner: B,
     bytes_read: u64,
+    /// 流的总字节数，用于按比例通知进度和判断读取结束
+    len: Option<u64>,
+    interval: Duration,
+    /// 上一次通知时的时间与已读取的字节数
+    last_notified: Option<(Instant, u64)>,
+    speed: SpeedWindow,
     progress: ProgressHandler,
+    finish: Option<FinishHandler>,
     start: OnceCell<Instant>,
+    hasher: Option<Sha256>,
+    throttle: Throttle,
 }
 
-impl ReadProgressStream {
-    pub(super) fn new(inner: ReqBody, progress: ProgressHandler) -> Self {
+impl<B> ReadProgressStream<B> {
+    pub(super) fn new(inner: B, progress: ProgressHandler) -> Self {
         ReadProgressStream {
             inner,
             progress,
+            finish: None,
             bytes_read: 0,
+            len: None,
+            interval: progress_interval(),
+            last_notified: None,
+            speed: SpeedWindow::new(),
             start: OnceCell::new(),
+            hasher: None,
+            throttle: Throttle::new(),
+        }
+    }
+
+    /// 设置流的总字节数，进度变化达到总长度的 1% 时立即通知，读取完毕时通知最后一次进度。
+    pub(super) fn with_len(mut self, len: u64) -> Self {
+        self.len = Some(len);
+        self
+    }
+
+    /// 与同一传输的其他请求共用单个传输限速的令牌桶。
+    pub(super) fn with_bucket(mut self, bucket: TransferBucket) -> Self {
+        self.throttle = Throttle::with_bucket(bucket);
+        self
+    }
+
+    /// 读取时同时计算数据的哈希，`hasher` 中可以已经包含之前接收的数据。
+    pub(super) fn with_hasher(mut self, hasher: Sha256) -> Self {
+        self.hasher = Some(hasher);
+        self
+    }
+
+    /// 流被丢弃时调用 `finish`。
+    ///
+    /// 发送响应时，客户端断开或所有数据发送完毕后流都会被丢弃，
+    /// 可以根据已读取的字节数判断是否发送完整。
+    pub(super) fn on_finish(mut self, finish: FinishHandler) -> Self {
+        self.finish = Some(finish);
+        self
+    }
+
+    pub(super) fn into_hasher(mut self) -> Option<Sha256> {
+        self.hasher.take()
+    }
+
+    /// 距上次通知超过间隔、进度变化足够大或读取结束时通知进度。
+    fn notify(&mut self, start: Instant, finished: bool) {
+        let now = Instant::now();
+        let bytes = self.bytes_read;
+
+        let finished = finished || self.len.is_some_and(|len| bytes >= len);
+
+        let due = match self.last_notified {
+            None => true,
+            Some((_, last_bytes)) if finished => bytes != last_bytes,
+            Some((time, last_bytes)) => {
+                now.duration_since(time) >= self.interval
+                    || self.len.is_some_and(|len| {
+                        (bytes - last_bytes) as f64 >= len as f64 * PROGRESS_STEP
+                    })
+            }
+        };
+        if !due {
+            return;
         }
+
+        // 第一次通知时以开始读取的时间作为采样起点
+        if self.last_notified.is_none() {
+            self.speed.update(start, 0);
+        }
+
+        let speed = self.speed.update(now, bytes);
+        self.last_notified = Some((now, bytes));
+
+        (self.progress)(Progress {
+            bytes,
+            speed,
+            average_speed: mb_per_sec(bytes, now.duration_since(start)),
+        });
+    }
+}
+
+/// 保留 io 错误的类型，其他错误转换为 [`std::io::ErrorKind::Other`]。
+fn into_io_error<E: Into<BoxedError>>(e: E) -> Error {
+    match e.into().downcast::<Error>() {
+        Ok(e) => *e,
+        Err(e) => Error::other(e),
     }
 }
 
-impl Stream for ReadProgressStream {
+impl<B> Stream for ReadProgressStream<B>
+where
+    B: Body<Data = Bytes> + Unpin,
+    B::Error: Into<BoxedError>,
+{
     type Item = Result<Bytes>;
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
         let start = self.start.get_or_init(|| Instant::now()).clone();
 
-        let body = &mut self.inner;
-
-        match Body::poll_frame(Pin::new(body), cx) {
-            Poll::Ready(Some(Ok(frame))) => {
-                let bytes: Bytes = frame.into_data().unwrap();
+        // 超过限速时等待令牌补足后再读取，上传时依靠 TCP 的流量控制让发送端减速
+        if self.throttle.poll_ready(cx).is_pending() {
+            return Poll::Pending;
+        }
 
-                let current_time = Instant::now();
-                let cost = current_time.duration_since(start);
+        loop {
+            let body = &mut self.inner;
 
-                let mut bytes_read = self.bytes_read;
+            let bytes: Bytes = match Body::poll_frame(Pin::new(body), cx) {
+                Poll::Ready(Some(Ok(frame))) => match frame.into_data() {
+                    Ok(bytes) => bytes,
+                    // 跳过 trailers 等非数据帧
+                    Err(_) => continue,
+                },
+                Poll::Ready(Some(Err(e))) => return Poll::Ready(Some(Err(into_io_error(e)))),
+                Poll::Ready(None) => {
+                    self.notify(start, true);
+                    return Poll::Ready(None);
+                }
+                Poll::Pending => return Poll::Pending,
+            };
 
-                bytes_read += bytes.len() as u64;
+            if let Some(hasher) = self.hasher.as_mut() {
+                hasher.update(&bytes);
+            }
 
-                (self.progress)(cost, bytes_read);
+            self.bytes_read += bytes.len() as u64;
+            self.throttle.consume(bytes.len() as u64);
+            self.notify(start, false);
 
-                self.bytes_read = bytes_read;
+            return Poll::Ready(Some(Ok(bytes)));
+        }
+    }
+}
 
-                Poll::Ready(Some(Ok(bytes)))
-            }
-            Poll::Ready(Some(Err(e))) => Poll::Ready(Some(Err(e))),
-            Poll::Ready(None) => Poll::Ready(None),
-            Poll::Pending => Poll::Pending,
+impl<B> Drop for ReadProgressStream<B> {
+    fn drop(&mut self) {
+        if let Some(finish) = self.finish.take() {
+            let cost = self.start.get().map(Instant::elapsed).unwrap_or_default();
+            finish(cost, self.bytes_read);
         }
     }
 }
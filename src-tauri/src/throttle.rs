@@ -0,0 +1,269 @@
+use std::future::Future;
+use std::io::Result;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{ready, Context, Poll};
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use futures::stream::Stream;
+use tokio::time::Sleep;
+
+/// 令牌桶最少可以积累的字节数，避免限速较低时每个分块都需要等待
+const MIN_BURST: u64 = 64 * 1024;
+
+/// 所有传输共用的限速（字节/秒），0 表示不限制
+static GLOBAL_LIMIT: AtomicU64 = AtomicU64::new(0);
+/// 每个传输单独的限速（字节/秒），0 表示不限制
+static TRANSFER_LIMIT: AtomicU64 = AtomicU64::new(0);
+
+/// 所有传输共用的令牌桶
+static GLOBAL_BUCKET: Mutex<TokenBucket> = Mutex::new(TokenBucket::new());
+
+/// 设置全局与单个传输的限速，单位为字节/秒，为空时不限制。
+///
+/// 对正在进行的传输立即生效。
+pub(crate) fn set_limits(global: Option<u64>, per_transfer: Option<u64>) {
+    GLOBAL_LIMIT.store(global.unwrap_or(0), Ordering::Relaxed);
+    TRANSFER_LIMIT.store(per_transfer.unwrap_or(0), Ordering::Relaxed);
+}
+
+struct TokenBucket {
+    /// 当前可用的字节数，透支时为负数
+    tokens: f64,
+    /// 上一次取令牌的时间，为空表示桶是满的
+    last: Option<Instant>,
+}
+
+impl TokenBucket {
+    const fn new() -> Self {
+        Self {
+            tokens: 0.,
+            last: None,
+        }
+    }
+
+    /// 按 `rate` 补充到 `now` 为止的令牌后取出 `n` 个，返回令牌补足前需要等待的时间。
+    ///
+    /// 令牌不足时允许透支，由之后的等待补偿，因此单个分块大于桶的容量时也不会阻塞。
+    fn consume(&mut self, now: Instant, rate: u64, n: u64) -> Duration {
+        // 最多积累 1/4 秒的流量
+        let burst = (rate / 4).max(MIN_BURST) as f64;
+
+        let available = match self.last {
+            None => burst,
+            Some(last) => {
+                (self.tokens + now.duration_since(last).as_secs_f64() * rate as f64).min(burst)
+            }
+        };
+
+        self.last = Some(now);
+        self.tokens = available - n as f64;
+
+        if self.tokens >= 0. {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / rate as f64)
+        }
+    }
+
+    fn reset(&mut self) {
+        self.last = None;
+    }
+}
+
+/// 单个传输的令牌桶。
+///
+/// 一个传输由多个请求组成时（如并发上传的分块），各请求共用同一个令牌桶，
+/// 单个传输的限速才会作用于整个文件。
+#[derive(Clone)]
+pub(crate) struct TransferBucket(Arc<Mutex<TokenBucket>>);
+
+impl TransferBucket {
+    pub(crate) fn new() -> Self {
+        Self(Arc::new(Mutex::new(TokenBucket::new())))
+    }
+}
+
+/// 读取数据流时的限速器，同时受全局限速与单个传输限速的约束。
+pub(super) struct Throttle {
+    bucket: TransferBucket,
+    delay: Option<Pin<Box<Sleep>>>,
+}
+
+impl Throttle {
+    pub(super) fn new() -> Self {
+        Self::with_bucket(TransferBucket::new())
+    }
+
+    /// 使用与其他请求共用的令牌桶。
+    pub(super) fn with_bucket(bucket: TransferBucket) -> Self {
+        Self {
+            bucket,
+            delay: None,
+        }
+    }
+
+    /// 等待上一次读取的数据所需的令牌补足。
+    pub(super) fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        if let Some(delay) = self.delay.as_mut() {
+            ready!(delay.as_mut().poll(cx));
+            self.delay = None;
+        }
+
+        Poll::Ready(())
+    }
+
+    /// 记录读取了 `n` 个字节，超过限速时下一次读取前需要等待。
+    pub(super) fn consume(&mut self, n: u64) {
+        let wait = self.wait(Instant::now(), n);
+
+        if !wait.is_zero() {
+            self.delay = Some(Box::pin(tokio::time::sleep(wait)));
+        }
+    }
+
+    /// 按当前的限速从全局和单个传输的令牌桶中取出 `n` 个令牌，返回需要等待的时间。
+    fn wait(&self, now: Instant, n: u64) -> Duration {
+        let mut wait = Duration::ZERO;
+
+        match GLOBAL_LIMIT.load(Ordering::Relaxed) {
+            0 => GLOBAL_BUCKET.lock().unwrap().reset(),
+            rate => wait = wait.max(GLOBAL_BUCKET.lock().unwrap().consume(now, rate, n)),
+        }
+
+        match TRANSFER_LIMIT.load(Ordering::Relaxed) {
+            0 => self.bucket.0.lock().unwrap().reset(),
+            rate => wait = wait.max(self.bucket.0.lock().unwrap().consume(now, rate, n)),
+        }
+
+        wait
+    }
+}
+
+/// 为字节流限速，用于没有经过 [`crate::stream::ReadProgressStream`] 的响应体。
+pub(crate) struct ThrottledStream<S> {
+    inner: S,
+    throttle: Throttle,
+}
+
+impl<S> ThrottledStream<S> {
+    pub(crate) fn new(inner: S) -> Self {
+        Self {
+            inner,
+            throttle: Throttle::new(),
+        }
+    }
+}
+
+impl<S> Stream for ThrottledStream<S>
+where
+    S: Stream<Item = Result<Bytes>> + Unpin,
+{
+    type Item = Result<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        ready!(self.throttle.poll_ready(cx));
+
+        let item = ready!(Pin::new(&mut self.inner).poll_next(cx));
+        if let Some(Ok(bytes)) = &item {
+            self.throttle.consume(bytes.len() as u64);
+        }
+
+        Poll::Ready(item)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u64 = 1024 * 1024;
+    /// `RATE` 对应的桶容量
+    const BURST: u64 = RATE / 4;
+
+    fn secs(s: f64) -> Duration {
+        Duration::from_secs_f64(s)
+    }
+
+    #[test]
+    fn burst() {
+        let now = Instant::now();
+        let mut bucket = TokenBucket::new();
+
+        // 桶满时可以立即读取 1/4 秒的流量
+        assert_eq!(bucket.consume(now, RATE, BURST), Duration::ZERO);
+        assert!(bucket.consume(now, RATE, 1) > Duration::ZERO);
+
+        // 限速较低时至少可以积累 MIN_BURST 字节
+        let mut bucket = TokenBucket::new();
+        assert_eq!(bucket.consume(now, 1024, MIN_BURST), Duration::ZERO);
+        assert_eq!(bucket.consume(now, 1024, 1024), secs(1.));
+    }
+
+    #[test]
+    fn wait_for_overdraft() {
+        let now = Instant::now();
+        let mut bucket = TokenBucket::new();
+
+        // 超出桶容量的部分按速率等待
+        assert_eq!(bucket.consume(now, RATE, BURST + RATE), secs(1.));
+        // 透支未补足前继续读取需要等待更久
+        assert_eq!(bucket.consume(now, RATE, RATE / 2), secs(1.5));
+    }
+
+    #[test]
+    fn refill() {
+        let now = Instant::now();
+        let mut bucket = TokenBucket::new();
+
+        assert_eq!(bucket.consume(now, RATE, BURST + RATE), secs(1.));
+        assert_eq!(bucket.consume(now + secs(0.5), RATE, 0), secs(0.5));
+        assert_eq!(bucket.consume(now + secs(1.), RATE, 0), Duration::ZERO);
+
+        // 空闲很久后最多积累 BURST 个令牌
+        let later = now + secs(60.);
+        assert_eq!(bucket.consume(later, RATE, BURST), Duration::ZERO);
+        assert!(bucket.consume(later, RATE, 1) > Duration::ZERO);
+
+        bucket.reset();
+        assert_eq!(bucket.consume(later, RATE, BURST), Duration::ZERO);
+    }
+
+    /// 限速是全局状态，修改限速的断言都放在这一个测试中
+    #[test]
+    fn change_limits_at_runtime() {
+        let now = Instant::now();
+        let throttle = Throttle::new();
+
+        set_limits(None, None);
+        assert_eq!(throttle.wait(now, 100 * RATE), Duration::ZERO);
+
+        set_limits(None, Some(RATE));
+        assert_eq!(throttle.wait(now, BURST + RATE), secs(1.));
+
+        // 新的速率对已透支的令牌立即生效
+        set_limits(None, Some(2 * RATE));
+        assert_eq!(throttle.wait(now, 0), secs(0.5));
+
+        set_limits(None, None);
+        assert_eq!(throttle.wait(now, 100 * RATE), Duration::ZERO);
+
+        // 共用令牌桶的请求合计受单个传输的限速约束
+        set_limits(None, Some(RATE));
+        let bucket = TransferBucket::new();
+        let a = Throttle::with_bucket(bucket.clone());
+        let b = Throttle::with_bucket(bucket);
+        assert_eq!(a.wait(now, BURST), Duration::ZERO);
+        assert_eq!(b.wait(now, RATE), secs(1.));
+        assert_eq!(Throttle::new().wait(now, BURST), Duration::ZERO);
+
+        // 全局限速由所有传输共用
+        set_limits(Some(RATE), None);
+        assert_eq!(Throttle::new().wait(now, BURST), Duration::ZERO);
+        assert_eq!(Throttle::new().wait(now, RATE), secs(1.));
+
+        set_limits(None, None);
+    }
+}
@@ -3,6 +3,7 @@ import { TbArrowsTransferUp, TbArrowsTransferDown } from "solid-icons/tb";
 import { BiRegularSun, BiSolidMoon } from "solid-icons/bi";
 import {
   LazyAboutButton,
+  LazyApproval,
   LazyButton,
   LazyDialog,
   LazyReceive,
@@ -105,6 +106,8 @@ const App = () => {
       >
         <About />
       </LazyDialog>
+
+      {suspense(<LazyApproval />)}
     </AppContext.Provider>
   );
 };
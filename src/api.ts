@@ -11,6 +11,26 @@ export const getQrCodeState = async (id: number) =>
     id,
   });
 
+export const cancelTransfer = async (id: number) =>
+  await invoke<boolean>("cancel_transfer", { id });
+
+export const pauseTransfer = async (id: number) =>
+  await invoke<boolean>("pause_transfer", { id });
+
+export const resumeTransfer = async (id: number) =>
+  await invoke<boolean>("resume_transfer", { id });
+
+export const respondUploadApproval = async (
+  id: number,
+  accept: boolean,
+  alwaysAllow: boolean,
+) =>
+  await invoke<boolean>("respond_upload_approval", {
+    id,
+    accept,
+    alwaysAllow,
+  });
+
 export const getUploadQrCode = async () =>
   await invoke<QrCode>("upload_qr_code");
 
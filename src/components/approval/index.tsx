@@ -0,0 +1,102 @@
+import { Show, createEffect, createSignal, onCleanup, useContext } from "solid-js";
+import { appWindow } from "@tauri-apps/api/window";
+import { LazyButton, LazyDialog } from "~/lazy";
+import { respondUploadApproval } from "~/api";
+import { AppContext } from "~/context";
+import "./index.scss";
+
+const baseClassName = "approval";
+
+const Approval = () => {
+  const { translations } = useContext(AppContext)!;
+
+  // 等待确认的上传，按到达顺序逐个确认
+  const [requests, setRequests] = createSignal<ApprovalRequest[]>([]);
+
+  const current = () => requests()[0];
+
+  const remove = (id: number) =>
+    setRequests((prev) => prev.filter((r) => r.id !== id));
+
+  createEffect(() => {
+    const unlisten = appWindow.listen<ApprovalRequest>(
+      "upload://approval",
+      (e) => setRequests((prev) => [...prev, e.payload]),
+    );
+
+    const unlistenClosed = appWindow.listen<number>(
+      "upload://approval-closed",
+      (e) => remove(e.payload),
+    );
+
+    onCleanup(() => {
+      unlisten.then((f) => f());
+      unlistenClosed.then((f) => f());
+    });
+  });
+
+  const respond = async (accept: boolean, alwaysAllow: boolean) => {
+    const request = current();
+    if (!request) return;
+
+    remove(request.id);
+
+    await respondUploadApproval(request.id, accept, alwaysAllow);
+
+    // 始终允许后，同一会话中其他等待确认的文件也一并接收
+    if (alwaysAllow) {
+      const others = requests().filter((r) => r.session === request.session);
+      others.forEach((r) => remove(r.id));
+      await Promise.all(
+        others.map((r) => respondUploadApproval(r.id, true, false)),
+      );
+    }
+  };
+
+  return (
+    // 需要明确接收或拒绝，点击遮罩不关闭
+    <LazyDialog show={!!current()} onClose={() => {}} showMask>
+      <Show when={current()}>
+        {(request) => (
+          <div class={baseClassName}>
+            <h3>{translations()?.approval_dialog_title}</h3>
+
+            <div class={`${baseClassName}-name`}>
+              {request().folder ? `${request().folder}/` : ""}
+              {request().name}
+            </div>
+
+            <div>
+              {translations()?.list_item_file_size_label}: {request().size}
+            </div>
+
+            <div>
+              {translations()?.approval_dialog_sender_label}:{" "}
+              {request().client ?? "-"}
+            </div>
+
+            <Show when={request().device}>
+              <div class={`${baseClassName}-device`}>{request().device}</div>
+            </Show>
+
+            <div class={`${baseClassName}-buttons`}>
+              <LazyButton onClick={() => respond(false, false)}>
+                {translations()?.approval_reject_button_text}
+              </LazyButton>
+
+              <LazyButton onClick={() => respond(true, true)}>
+                {translations()?.approval_always_allow_button_text}
+              </LazyButton>
+
+              <LazyButton class="fill" onClick={() => respond(true, false)}>
+                {translations()?.approval_accept_button_text}
+              </LazyButton>
+            </div>
+          </div>
+        )}
+      </Show>
+    </LazyDialog>
+  );
+};
+
+export default Approval;
@@ -10,7 +10,7 @@ import {
 } from "~/lazy";
 import "./index.scss";
 import { AiFillCopy } from "solid-icons/ai";
-import { createSignal, useContext } from "solid-js";
+import { Show, createSignal, useContext } from "solid-js";
 import { AppContext } from "~/context";
 
 interface QRCodeProps {
@@ -63,6 +63,13 @@ const QRCode = ({ qrcode }: QRCodeProps) => {
         </LazyTooltip>
       </LazySpace>
 
+      <Show when={qrcode.fingerprint}>
+        <div class={`${baseClassName}-fingerprint`}>
+          <div>{translations()?.qrcode_page_fingerprint_label}</div>
+          <code>{qrcode.fingerprint}</code>
+        </div>
+      </Show>
+
       <LazyToast
         placement="bottom"
         open={showToast()}
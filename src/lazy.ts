@@ -66,6 +66,7 @@ export const LazyDialog = lazy(
 
 export const LazyQrcode = lazy(() => import("~/components/qrcode"));
 export const LazyAboutButton = lazy(() => import("~/components/aboutButton"));
+export const LazyApproval = lazy(() => import("~/components/approval"));
 
 export const LazySend = lazy(() => import("~/pages/send"));
 // export const LazySendFileList = lazy(() => import("~/pages/send/list"));
@@ -1,6 +1,11 @@
 import { Show, useContext } from "solid-js";
 import { open } from "@tauri-apps/api/shell";
-import { AiFillCheckCircle } from "solid-icons/ai";
+import {
+  AiFillCheckCircle,
+  AiOutlineCloseCircle,
+  AiOutlinePauseCircle,
+  AiOutlinePlayCircle,
+} from "solid-icons/ai";
 import fileType from "./fileType";
 import {
   LazyLink,
@@ -18,7 +23,13 @@ interface FileListItemProps {
   name: string;
   percent: number;
   speed?: number;
+  /** 预计剩余时间（秒） */
+  eta?: number | null;
   size: string;
+  paused?: boolean;
+  onPause?: () => void;
+  onResume?: () => void;
+  onCancel?: () => void;
 }
 
 const FileListItem = (props: FileListItemProps) => {
@@ -61,8 +72,51 @@ const FileListItem = (props: FileListItemProps) => {
         </LazySpace>
       }
       extra={
-        props.speed ? (
-          <span class="speed">{props.speed.toFixed(1)} MB/s</span>
+        props.percent < 100 ? (
+          <span class="controls">
+            <span class="speed">
+              {(props.paused ? 0 : props.speed ?? 0).toFixed(1)} MB/s
+            </span>
+
+            <Show when={!props.paused && props.eta != null}>
+              <span class="eta">{formatEta(props.eta!)}</span>
+            </Show>
+
+            <Show when={props.onPause && props.onResume}>
+              <LazyTooltip
+                text={
+                  props.paused
+                    ? translations()!.receive_page_resume_tooltip
+                    : translations()!.receive_page_pause_tooltip
+                }
+                placement="top"
+              >
+                <span
+                  class="control"
+                  onClick={() =>
+                    props.paused ? props.onResume!() : props.onPause!()
+                  }
+                >
+                  {props.paused ? (
+                    <AiOutlinePlayCircle />
+                  ) : (
+                    <AiOutlinePauseCircle />
+                  )}
+                </span>
+              </LazyTooltip>
+            </Show>
+
+            <Show when={props.onCancel}>
+              <LazyTooltip
+                text={translations()!.receive_page_cancel_tooltip}
+                placement="top"
+              >
+                <span class="control danger" onClick={() => props.onCancel!()}>
+                  <AiOutlineCloseCircle />
+                </span>
+              </LazyTooltip>
+            </Show>
+          </span>
         ) : (
           <span class="done">
             <AiFillCheckCircle />
@@ -74,6 +128,17 @@ const FileListItem = (props: FileListItemProps) => {
   );
 };
 
+const formatEta = (seconds: number): string => {
+  const total = Math.ceil(seconds);
+  const h = Math.floor(total / 3600);
+  const m = Math.floor((total % 3600) / 60);
+  const s = total % 60;
+
+  const pad = (n: number) => n.toString().padStart(2, "0");
+
+  return h ? `${h}:${pad(m)}:${pad(s)}` : `${pad(m)}:${pad(s)}`;
+};
+
 const getExtension = (name: string): string => {
   const dotIndex = name.lastIndexOf(".");
 
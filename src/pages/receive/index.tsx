@@ -1,6 +1,8 @@
 import {
+  For,
   Match,
   Switch,
+  createMemo,
   createEffect,
   createSignal,
   onCleanup,
@@ -10,7 +12,13 @@ import {
   useContext,
 } from "solid-js";
 import { appWindow } from "@tauri-apps/api/window";
-import { getUploadQrCode, getQrCodeState } from "~/api";
+import {
+  getUploadQrCode,
+  getQrCodeState,
+  cancelTransfer,
+  pauseTransfer,
+  resumeTransfer,
+} from "~/api";
 import FileListItem from "./fileListItem";
 import "./index.scss";
 import { suspense } from "~/advance";
@@ -25,7 +33,11 @@ import {
   LazyAboutButton,
 } from "~/lazy";
 import { createStore } from "solid-js/store";
-import { AiFillDelete, AiOutlineHome } from "solid-icons/ai";
+import {
+  AiFillDelete,
+  AiOutlineFolder,
+  AiOutlineHome,
+} from "solid-icons/ai";
 import { AppContext } from "~/context";
 
 const Receive = () => {
@@ -35,6 +47,8 @@ const Receive = () => {
 
   const [taskList, setTaskList] = createStore<TaskMessage[]>([]);
   const [fileList, setFileList] = createStore<Omit<TaskMessage, "speed">[]>([]);
+  // 已暂停的传输 id
+  const [paused, setPaused] = createSignal<number[]>([]);
 
   onMount(() => {
     if (qrcode() || taskList.length || fileList.length) return;
@@ -46,9 +60,10 @@ const Receive = () => {
     const unlisten = appWindow.listen<TaskMessage>("upload://progress", (e) => {
       if (qrcode()) setQrcode(null);
 
-      const { path, percent, aborted } = e.payload;
+      // 同名文件可能同时上传，保存路径也可能在完成时改变，所以按传输 id 区分任务
+      const { id, percent, aborted } = e.payload;
 
-      const taskIndex = taskList.findIndex((prev) => prev.path === path);
+      const taskIndex = taskList.findIndex((prev) => prev.id === id);
       if (taskIndex === -1) {
         setTaskList(taskList.length, e.payload);
       } else {
@@ -56,19 +71,24 @@ const Receive = () => {
           ...item,
           percent: e.payload.percent,
           speed: e.payload.speed,
+          average_speed: e.payload.average_speed,
+          transferred: e.payload.transferred,
+          eta: e.payload.eta,
         }));
       }
 
       if (aborted) {
-        setFileList((prev) => prev.filter((i) => i.path !== path));
-        setTaskList((prev) => prev.filter((i) => i.path !== path));
+        setFileList((prev) => prev.filter((i) => i.id !== id));
+        setTaskList((prev) => prev.filter((i) => i.id !== id));
+        setPaused((prev) => prev.filter((i) => i !== id));
         return;
       }
 
       if (percent === 100) {
-        setTaskList((prev) => prev.filter((i) => i.path !== path));
+        setTaskList((prev) => prev.filter((i) => i.id !== id));
+        setPaused((prev) => prev.filter((i) => i !== id));
 
-        const doneIndex = fileList.findIndex((prev) => prev.path === path);
+        const doneIndex = fileList.findIndex((prev) => prev.id === id);
         if (doneIndex === -1) setFileList(fileList.length, e.payload);
       }
     });
@@ -78,6 +98,29 @@ const Receive = () => {
     });
   });
 
+  createEffect(() => {
+    if (!qrcode()) return;
+
+    // 网络地址变化后旧二维码中的地址已无法访问
+    const unlisten = appWindow.listen<string | null>("network://changed", () =>
+      getUploadQrCode().then((c) => setQrcode(c)),
+    );
+
+    // 二维码过期后自动生成新的二维码
+    const unlistenExpired = appWindow.listen<number>(
+      "session://expired",
+      (e) => {
+        if (qrcode()?.id !== e.payload) return;
+        getUploadQrCode().then((c) => setQrcode(c));
+      },
+    );
+
+    onCleanup(() => {
+      unlisten.then((f) => f());
+      unlistenExpired.then((f) => f());
+    });
+  });
+
   createEffect(() => {
     const code = qrcode();
     if (!code) return;
@@ -113,12 +156,51 @@ const Receive = () => {
         onClick={() => {
           setTaskList([]);
           setFileList([]);
+          setPaused([]);
           goHome();
         }}
       />
     </LazyFloatButtonGroup>
   ));
 
+  // 上传文件夹时按顶层文件夹分组显示，单独上传的文件排在最前
+  const folders = createMemo(() => {
+    const names = new Set<string>();
+    for (const item of [...fileList, ...taskList]) names.add(item.folder ?? "");
+    return [...names].sort((a, b) => a.localeCompare(b));
+  });
+
+  const renderFile = (item: Omit<TaskMessage, "speed">, index: number) => (
+    <FileListItem
+      index={index}
+      name={item.name}
+      percent={100}
+      size={item.size}
+      path={item.path}
+    />
+  );
+
+  const renderTask = (item: TaskMessage) => (
+    <FileListItem
+      path={item.path}
+      name={item.name}
+      percent={Math.round(item.percent)}
+      speed={item.speed}
+      eta={item.eta}
+      size={item.size}
+      paused={paused().includes(item.id)}
+      onPause={async () => {
+        if (await pauseTransfer(item.id))
+          setPaused((prev) => [...prev, item.id]);
+      }}
+      onResume={async () => {
+        if (await resumeTransfer(item.id))
+          setPaused((prev) => prev.filter((id) => id !== item.id));
+      }}
+      onCancel={() => cancelTransfer(item.id)}
+    />
+  );
+
   return (
     <Switch>
       <Match when={qrcode() !== null}>
@@ -152,31 +234,32 @@ const Receive = () => {
           {suspense(<LazyReceiveHeader />)}
 
           <ul class="receive-file-list">
-            <LazyList
-              dataSource={fileList}
-              renderItem={(item, index) => (
-                <FileListItem
-                  index={index}
-                  name={item.name}
-                  percent={100}
-                  size={item.size}
-                  path={item.path}
-                />
-              )}
-            />
+            <For each={folders()}>
+              {(folder) => (
+                <>
+                  <Show when={folder}>
+                    <li class="receive-folder">
+                      <AiOutlineFolder />
+                      <span>{folder}</span>
+                    </li>
+                  </Show>
 
-            <LazyList
-              dataSource={taskList}
-              renderItem={(item) => (
-                <FileListItem
-                  path={item.path}
-                  name={item.name}
-                  percent={Math.round(item.percent)}
-                  speed={item.speed}
-                  size={item.size}
-                />
+                  <LazyList
+                    dataSource={fileList.filter(
+                      (i) => (i.folder ?? "") === folder,
+                    )}
+                    renderItem={renderFile}
+                  />
+
+                  <LazyList
+                    dataSource={taskList.filter(
+                      (i) => (i.folder ?? "") === folder,
+                    )}
+                    renderItem={renderTask}
+                  />
+                </>
               )}
-            />
+            </For>
           </ul>
         </LazyFlex>
 
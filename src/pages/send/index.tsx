@@ -12,6 +12,7 @@ import {
   AiOutlineCloseCircle,
   AiOutlineHome,
 } from "solid-icons/ai";
+import { createStore } from "solid-js/store";
 import { appWindow } from "@tauri-apps/api/window";
 import { TauriEvent } from "@tauri-apps/api/event";
 import "./index.scss";
@@ -28,6 +29,7 @@ import {
   LazyLink,
   LazyList,
   LazyListItem,
+  LazyProgress,
   LazyQrcode,
   LazyTooltip,
   LazyTypographyText,
@@ -42,6 +44,31 @@ const Send = () => {
   const [files, setFiles] = createSignal<SendFile[]>([]);
 
   const [qrcode, setQrcode] = createSignal<QrCode | null>(null);
+  // 已有设备扫码连接，之后生成的二维码供其他设备使用
+  const [connected, setConnected] = createSignal(false);
+  // 手机端的下载进度，键为文件路径
+  const [downloads, setDownloads] = createStore<Record<string, TaskMessage>>(
+    {},
+  );
+
+  createEffect(() => {
+    const unlisten = appWindow.listen<TaskMessage>(
+      "download://progress",
+      (e) => setDownloads(e.payload.path, e.payload),
+    );
+
+    onCleanup(() => {
+      unlisten.then((f) => f());
+    });
+  });
+
+  const downloadState = (download: TaskMessage) => {
+    if (download.aborted)
+      return translations()?.send_page_download_interrupted_label;
+    if (download.percent === 100)
+      return translations()?.send_page_download_completed_label;
+    return `${download.percent}% ${download.speed.toFixed(1)} MB/s`;
+  };
 
   createEffect(() => {
     const unlisten = appWindow.listen<string[]>(
@@ -67,14 +94,34 @@ const Send = () => {
       const used = await getQrCodeState(code.id);
 
       if (used) {
-        clearTimeout(timer);
+        clearInterval(timer);
+        // 保留文件列表，可以为其他设备生成新的二维码
+        setConnected(true);
         setQrcode(null);
       }
     }, 500);
 
+    onCleanup(() => clearInterval(timer));
+  });
+
+  createEffect(() => {
+    if (!qrcode()) return;
+
+    const unlisten = appWindow.listen<string | null>("network://changed", () =>
+      newSendFilesQrCode(),
+    );
+
+    const unlistenExpired = appWindow.listen<number>(
+      "session://expired",
+      (e) => {
+        if (qrcode()?.id !== e.payload) return;
+        newSendFilesQrCode();
+      },
+    );
+
     onCleanup(() => {
-      clearTimeout(timer);
-      location.reload();
+      unlisten.then((f) => f());
+      unlistenExpired.then((f) => f());
     });
   });
 
@@ -157,8 +204,23 @@ const Send = () => {
                             {translations()?.list_item_file_type_label}:{" "}
                             {file.extension}
                           </span>
+                          <Show when={downloads[file.path]}>
+                            {(download) => (
+                              <>
+                                &nbsp;&nbsp;&nbsp;&nbsp;
+                                <span>{downloadState(download())}</span>
+                              </>
+                            )}
+                          </Show>
                         </>
                       }
+                      foot={
+                        <Show when={downloads[file.path]}>
+                          {(download) => (
+                            <LazyProgress percent={download().percent} />
+                          )}
+                        </Show>
+                      }
                       extra={[
                         <LazyButton
                           class="delete-file"
@@ -175,7 +237,9 @@ const Send = () => {
                 />
 
                 <LazyTypographyText type="secondary">
-                  {translations()?.send_page_drop_description}
+                  {connected()
+                    ? translations()?.send_page_connected_description
+                    : translations()?.send_page_drop_description}
                 </LazyTypographyText>
               </LazyFlex>
             ) : (
@@ -191,7 +255,9 @@ const Send = () => {
             block
             disabled={isEmpty()}
           >
-            {translations()?.ok_button_text}
+            {connected()
+              ? translations()?.send_page_new_qrcode_button_text
+              : translations()?.ok_button_text}
           </LazyButton>
         </LazyFlex>
       </Show>
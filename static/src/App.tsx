@@ -1,4 +1,11 @@
-import { Match, Switch, createEffect, createSignal, onMount } from "solid-js";
+import {
+  Match,
+  Show,
+  Switch,
+  createEffect,
+  createSignal,
+  onMount,
+} from "solid-js";
 import { BiRegularSun, BiSolidMoon } from "solid-icons/bi";
 import Result from "~/components/result";
 import Send from "~/pages/send";
@@ -15,6 +22,8 @@ const App = () => {
   const url = new URL(href);
   const params = new URLSearchParams(url.search);
   const mode = params.get("mode") as Mode | null;
+  // 启用 HTTPS 时二维码中的证书指纹，用于与浏览器显示的证书核对
+  const fingerprint = params.get("fp");
 
   const locale = getLocale();
 
@@ -80,6 +89,13 @@ const App = () => {
           <Receive />
         </Match>
       </Switch>
+
+      <Show when={fingerprint}>
+        <div class="fingerprint">
+          <div>{locale.certificate_fingerprint_label}</div>
+          <code>{fingerprint}</code>
+        </div>
+      </Show>
     </LocaleContext.Provider>
   );
 };
@@ -1,5 +1,5 @@
 import { BsStopCircle } from "solid-icons/bs";
-import { AiFillCheckCircle } from "solid-icons/ai";
+import { AiFillCheckCircle, AiFillCloseCircle } from "solid-icons/ai";
 import fileType from "~/pages/receive/fileType";
 import formatFileSize from "./fileSize";
 import Space from "~/components/space";
@@ -23,6 +23,7 @@ interface FileItemProps {
   file: File;
   speed?: number;
   percent?: number;
+  error?: BadRequest;
   abort?: () => void;
 }
 
@@ -35,7 +36,7 @@ const FileItem = (props: FileItemProps) => {
       title={
         <span class="filename">
           <span class="label">{props.index + 1}.</span>
-          {props.file.name}
+          {props.file.webkitRelativePath || props.file.name}
         </span>
       }
       description={
@@ -65,6 +66,10 @@ const FileItem = (props: FileItemProps) => {
             </span>
             <span class="speed">{props.speed.toFixed(1)} MB/s</span>
           </Space>
+        ) : props.error ? (
+          <span class="failed">
+            <AiFillCloseCircle />
+          </span>
         ) : props.percent === undefined ? (
           <span class="waiting">
             <DotLoading />
@@ -75,7 +80,19 @@ const FileItem = (props: FileItemProps) => {
           </span>
         )
       }
-      foot={<Progress percent={props.percent} />}
+      foot={
+        <>
+          <Show when={props.error}>
+            {(error) => (
+              <div class="error">
+                {error().error}
+                <Show when={error().advice}>，{error().advice}</Show>
+              </div>
+            )}
+          </Show>
+          <Progress percent={props.percent} />
+        </>
+      }
     />
   );
 };
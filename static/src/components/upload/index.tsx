@@ -1,57 +1,42 @@
-import {
-  type JSX,
-  createEffect,
-  createSignal,
-  createUniqueId,
-  useContext,
-} from "solid-js";
+import { type JSX, Show, createUniqueId, useContext } from "solid-js";
 import { createStore } from "solid-js/store";
-import { getFileItemIndex } from "./util";
-import request from "./request";
+import uploadFile from "./uploader";
 import asyncPool from "~/components/upload/asyncPool";
 import ErrorBlock from "../error-block";
 import List from "../list";
 import FileItem from "./fileItem";
 import "./index.scss";
 import Button from "../button";
-import { AiOutlinePlus } from "solid-icons/ai";
+import { AiOutlineFolderAdd, AiOutlinePlus } from "solid-icons/ai";
 import LocaleContext from "~/context";
 
+/** 同时上传的文件数 */
+const CONCURRENCY = 2;
+
+/** 部分浏览器（如 iOS Safari）不支持选择文件夹 */
+const supportsFolder = "webkitdirectory" in document.createElement("input");
+
 interface UploadProps {
   action: string;
-  headers?: Record<string, string>;
-  withCredentials?: boolean;
-  method?: UploadRequestOption["method"];
 }
 
-const Upload = ({ action, headers, withCredentials, method }: UploadProps) => {
+const Upload = ({ action }: UploadProps) => {
   const locale = useContext(LocaleContext)!;
 
   let fileInput: HTMLInputElement | undefined;
+  let folderInput: HTMLInputElement | undefined;
 
   const [fileItems, setFileItems] = createStore<FileListItem[]>([]);
 
-  const [requestTasks, setRequestTasks] = createSignal<RequestTask[]>([]);
-
-  const appendTask = (task: RequestTask) =>
-    setRequestTasks((pre) => [...pre, task]);
+  // 进行中的上传，用于中断
+  const controllers = new Map<string, AbortController>();
 
-  const onProgress = (e: { percent?: number; speed?: number }, file: File) => {
-    const idx = getFileItemIndex(file, fileItems);
-    if (idx === -1) {
-      return;
-    }
+  const update = (id: string, value: Partial<FileListItem>) =>
+    setFileItems((item) => item.id === id, value);
 
-    setFileItems(idx, () => ({ percent: e.percent, speed: e.speed }));
-  };
-
-  const onSuccess = (fileItem: FileListItem) => {
-    const idx = getFileItemIndex(fileItem.file, fileItems);
-    if (idx === -1) {
-      return;
-    }
-
-    setFileItems(idx, () => ({ percent: 100, speed: undefined }));
+  const abortAll = () => {
+    controllers.forEach((c) => c.abort());
+    controllers.clear();
   };
 
   const onChange: JSX.ChangeEventHandlerUnion<HTMLInputElement, Event> = (
@@ -61,76 +46,72 @@ const Upload = ({ action, headers, withCredentials, method }: UploadProps) => {
 
     if (!files) return;
 
-    const fileItems: FileListItem[] = [];
+    const items: FileListItem[] = [];
     for (const file of files) {
-      fileItems.push({ file, id: createUniqueId() });
+      items.push({ file, id: createUniqueId() });
     }
 
-    for (const f of fileItems) {
-      send(f);
-    }
+    setFileItems(items);
 
-    setFileItems(fileItems);
+    asyncPool(CONCURRENCY, items, send);
   };
 
   const onClick: JSX.EventHandlerUnion<HTMLDivElement, MouseEvent> = (e) => {
-    const target = e.target as HTMLElement;
-
-    if (
-      target &&
-      (target.tagName === "BUTTON" ||
-        target.parentElement?.tagName === "BUTTON" ||
-        target.parentElement?.parentElement?.tagName === "BUTTON")
-    ) {
+    const button = (e.target as HTMLElement).closest("button");
+
+    if (button) {
+      const input = button.classList.contains("folder-button")
+        ? folderInput
+        : fileInput;
+
+      abortAll();
       setFileItems([]);
-      setRequestTasks([]);
       // biome-ignore lint/style/noNonNullAssertion: <explanation>
-      fileInput!.value = ""; // 点击按钮后清空 input files
-      fileInput?.click();
-      target.blur();
+      input!.value = ""; // 点击按钮后清空 input files
+      input?.click();
+      button.blur();
     }
   };
 
-  const send = (fileItem: FileListItem) => {
-    const option: UploadRequestOption = {
-      id: fileItem.id,
-      action,
-      file: fileItem.file,
-      method: method || "POST",
-      headers,
-      withCredentials,
-      onProgress,
-      onError: (e) => {
-        console.log(e);
-        // todo: 反馈上传错误
-      },
-      onSuccess: () => {
-        onSuccess(fileItem);
-      },
-    };
-
-    request(option, appendTask);
+  const send = async (fileItem: FileListItem) => {
+    // 等待期间已被删除或重新选择了文件
+    if (!fileItems.some((i) => i.id === fileItem.id)) return;
+
+    const controller = new AbortController();
+    controllers.set(fileItem.id, controller);
+
+    const { size } = fileItem.file;
+
+    try {
+      await uploadFile(fileItem.file, {
+        action,
+        signal: controller.signal,
+        onProgress: (uploaded, speed) =>
+          update(fileItem.id, {
+            percent: size ? (uploaded / size) * 100 : 100,
+            speed,
+          }),
+      });
+
+      update(fileItem.id, { percent: 100, speed: undefined });
+    } catch (e) {
+      const err = e as UploadRequestError;
+      console.log(err);
+
+      if (err.aborted) return;
+
+      // 服务端返回的错误信息，如文件过大或磁盘空间不足
+      const error =
+        err.body && typeof err.body === "object" && "error" in err.body
+          ? (err.body as BadRequest)
+          : { error: locale.upload_failed, advice: null };
+
+      update(fileItem.id, { speed: undefined, error });
+    } finally {
+      controllers.delete(fileItem.id);
+    }
   };
 
-  createEffect(() => {
-    // 任务数量与文件数量相等后才发送请求
-    if (!fileItems.length || fileItems.length !== requestTasks().length) return;
-
-    asyncPool(
-      2, // 并发数量，应该根据实际场景判断，比如通过 fileList 中的文件尺寸进行判断，当超过某个阈值时减小并发量
-      requestTasks(),
-      (item) =>
-        new Promise<void>((resolve) => {
-          const xhr = item.xhr;
-
-          item.done = resolve;
-          item.start = new Date().getTime() / 1000;
-
-          xhr.send(item.data);
-        }),
-    );
-  });
-
   return (
     <div id="upload" onClick={onClick}>
       <div class="upload-file-list">
@@ -152,11 +133,10 @@ const Upload = ({ action, headers, withCredentials, method }: UploadProps) => {
                 file={item.file}
                 percent={item.percent}
                 speed={item.speed}
+                error={item.error}
                 abort={() => {
                   // 中断请求
-                  requestTasks()
-                    .find((t) => t.id === item.id)
-                    ?.xhr.abort();
+                  controllers.get(item.id)?.abort();
                   // 删除文件
                   setFileItems((pre) => pre.filter((i) => i.id !== item.id));
                 }}
@@ -172,6 +152,16 @@ const Upload = ({ action, headers, withCredentials, method }: UploadProps) => {
         style={{ display: "none" }}
         onChange={onChange}
       />
+      <input
+        type="file"
+        multiple
+        ref={(el) => {
+          folderInput = el;
+          el.webkitdirectory = true;
+        }}
+        style={{ display: "none" }}
+        onChange={onChange}
+      />
 
       <Button
         class="submit-button"
@@ -179,6 +169,14 @@ const Upload = ({ action, headers, withCredentials, method }: UploadProps) => {
       >
         <AiOutlinePlus />
       </Button>
+      <Show when={supportsFolder}>
+        <Button
+          class="submit-button folder-button"
+          disabled={fileItems.findIndex((f) => f.speed !== undefined) >= 0}
+        >
+          <AiOutlineFolderAdd />
+        </Button>
+      </Show>
     </div>
   );
 };
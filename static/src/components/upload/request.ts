@@ -1,9 +1,11 @@
-function getError(option: UploadRequestOption, xhr: XMLHttpRequest) {
-  const msg = `cannot ${option.method} ${option.action} ${xhr.status}'`;
+function getError(option: RequestOption, xhr: XMLHttpRequest, aborted = false) {
+  const msg = `cannot ${option.method} ${option.url} ${xhr.status}'`;
   const err = new Error(msg) as UploadRequestError;
   err.status = xhr.status;
   err.method = option.method;
-  err.url = option.action;
+  err.url = option.url;
+  err.body = getBody(xhr);
+  err.aborted = aborted;
   return err;
 }
 
@@ -20,73 +22,67 @@ function getBody(xhr: XMLHttpRequest) {
   }
 }
 
-const upload = (
-  option: UploadRequestOption,
-  appendTask: (task: RequestTask) => void,
-) => {
-  const xhr = new XMLHttpRequest();
+/**
+ * 发送一个请求，2xx 以外的状态码、网络错误和中断都会被 reject。
+ *
+ * `signal` 中断时同时中断请求。
+ */
+const request = (option: RequestOption): Promise<XMLHttpRequest> =>
+  new Promise((resolve, reject) => {
+    const xhr = new XMLHttpRequest();
+
+    if (option.signal?.aborted) {
+      return reject(getError(option, xhr, true));
+    }
 
-  const task: RequestTask = { id: option.id, xhr, data: option.file };
+    if (option.onProgress && xhr.upload) {
+      xhr.upload.onprogress = (e) => option.onProgress!(e.loaded);
+    }
 
-  if (option.onProgress && xhr.upload) {
-    xhr.upload.onprogress = function progress(e: UploadProgressEvent) {
-      if (e.total! > 0) {
-        e.percent = (e.loaded! / e.total!) * 100;
+    const abort = () => xhr.abort();
+    option.signal?.addEventListener("abort", abort);
 
-        const now = new Date().getTime() / 1000;
-        const delta = now - task.start!;
+    const cleanup = () => option.signal?.removeEventListener("abort", abort);
 
-        if (delta) e.speed = e.loaded! / 1024 / 1024 / delta;
-        else e.speed = 0;
-      }
-      option.onProgress(e, option.file);
+    xhr.onabort = () => {
+      cleanup();
+      reject(getError(option, xhr, true));
     };
-  }
 
-  xhr.onabort = () => {
-    task.done!();
-  };
-
-  xhr.onerror = function error(e) {
-    option.onError(e);
-    task.done!();
-  };
+    xhr.onerror = () => {
+      cleanup();
+      reject(getError(option, xhr));
+    };
 
-  xhr.onload = function onload() {
-    // 代码执行到这里时 done 一定存在
-    task.done!();
+    xhr.onload = function onload() {
+      cleanup();
 
-    // allow success when 2xx status
-    // see https://github.com/react-component/upload/issues/34
-    if (xhr.status < 200 || xhr.status >= 300) {
-      return option.onError!(getError(option, xhr), getBody(xhr));
-    }
+      // allow success when 2xx status
+      // see https://github.com/react-component/upload/issues/34
+      if (xhr.status < 200 || xhr.status >= 300) {
+        return reject(getError(option, xhr));
+      }
 
-    return option.onSuccess(getBody(xhr), xhr);
-  };
+      resolve(xhr);
+    };
 
-  xhr.open(option.method, option.action + "?name=" + option.file.name, true);
+    xhr.open(option.method, option.url, true);
 
-  // Has to be after `.open()`. See https://github.com/enyo/dropzone/issues/179
-  if (option.withCredentials && "withCredentials" in xhr) {
-    xhr.withCredentials = true;
-  }
+    const headers = option.headers || {};
 
-  const headers = option.headers || {};
+    // when set headers['X-Requested-With'] = null , can close default XHR header
+    // see https://github.com/react-component/upload/issues/33
+    if (headers["X-Requested-With"] !== null) {
+      xhr.setRequestHeader("X-Requested-With", "XMLHttpRequest");
+    }
 
-  // when set headers['X-Requested-With'] = null , can close default XHR header
-  // see https://github.com/react-component/upload/issues/33
-  if (headers["X-Requested-With"] !== null) {
-    xhr.setRequestHeader("X-Requested-With", "XMLHttpRequest");
-  }
+    Object.keys(headers).forEach((h) => {
+      if (headers[h] !== null) {
+        xhr.setRequestHeader(h, headers[h]);
+      }
+    });
 
-  Object.keys(headers).forEach((h) => {
-    if (headers[h] !== null) {
-      xhr.setRequestHeader(h, headers[h]);
-    }
+    xhr.send(option.body ?? null);
   });
 
-  appendTask(task);
-};
-
-export default upload;
+export default request;
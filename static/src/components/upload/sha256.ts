@@ -0,0 +1,144 @@
+// 通过局域网 HTTP 访问时页面不是安全上下文，无法使用 crypto.subtle，
+// 且 crypto.subtle 不支持增量计算，大文件无法一次读入内存
+
+const K = new Uint32Array([
+  0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1,
+  0x923f82a4, 0xab1c5ed5, 0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3,
+  0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174, 0xe49b69c1, 0xefbe4786,
+  0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+  0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147,
+  0x06ca6351, 0x14292967, 0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13,
+  0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85, 0xa2bfe8a1, 0xa81a664b,
+  0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+  0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a,
+  0x5b9cca4f, 0x682e6ff3, 0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208,
+  0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+]);
+
+/** 每次读取的字节数 */
+const READ_SIZE = 4 * 1024 * 1024;
+
+const rotr = (x: number, n: number) => (x >>> n) | (x << (32 - n));
+
+export class Sha256 {
+  private state = new Uint32Array([
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c,
+    0x1f83d9ab, 0x5be0cd19,
+  ]);
+  private w = new Uint32Array(64);
+  /** 未满 64 字节的剩余数据 */
+  private buffer = new Uint8Array(64);
+  private buffered = 0;
+  private length = 0;
+
+  update(data: Uint8Array) {
+    this.length += data.length;
+
+    let i = 0;
+    if (this.buffered > 0) {
+      const n = Math.min(64 - this.buffered, data.length);
+      this.buffer.set(data.subarray(0, n), this.buffered);
+      this.buffered += n;
+      i = n;
+      if (this.buffered < 64) return;
+      this.block(this.buffer, 0);
+      this.buffered = 0;
+    }
+
+    for (; i + 64 <= data.length; i += 64) {
+      this.block(data, i);
+    }
+
+    this.buffer.set(data.subarray(i));
+    this.buffered = data.length - i;
+  }
+
+  /** 返回十六进制的哈希，之后不能再调用 {@link update} */
+  hex() {
+    const bits = this.length * 8;
+    const padding = new Uint8Array(this.buffered < 56 ? 64 : 128);
+    padding.set(this.buffer.subarray(0, this.buffered));
+    padding[this.buffered] = 0x80;
+
+    const view = new DataView(padding.buffer);
+    view.setUint32(padding.length - 8, Math.floor(bits / 0x100000000));
+    view.setUint32(padding.length - 4, bits >>> 0);
+
+    for (let i = 0; i < padding.length; i += 64) {
+      this.block(padding, i);
+    }
+
+    return Array.from(this.state, (x) => x.toString(16).padStart(8, "0")).join(
+      "",
+    );
+  }
+
+  private block(data: Uint8Array, offset: number) {
+    const w = this.w;
+    for (let i = 0; i < 16; i++) {
+      const j = offset + i * 4;
+      w[i] =
+        (data[j] << 24) | (data[j + 1] << 16) | (data[j + 2] << 8) | data[j + 3];
+    }
+    for (let i = 16; i < 64; i++) {
+      const s0 = rotr(w[i - 15], 7) ^ rotr(w[i - 15], 18) ^ (w[i - 15] >>> 3);
+      const s1 = rotr(w[i - 2], 17) ^ rotr(w[i - 2], 19) ^ (w[i - 2] >>> 10);
+      w[i] = w[i - 16] + s0 + w[i - 7] + s1;
+    }
+
+    let [a, b, c, d, e, f, g, h] = this.state;
+    for (let i = 0; i < 64; i++) {
+      const s1 = rotr(e, 6) ^ rotr(e, 11) ^ rotr(e, 25);
+      const ch = (e & f) ^ (~e & g);
+      const t1 = (h + s1 + ch + K[i] + w[i]) | 0;
+      const s0 = rotr(a, 2) ^ rotr(a, 13) ^ rotr(a, 22);
+      const maj = (a & b) ^ (a & c) ^ (b & c);
+      const t2 = (s0 + maj) | 0;
+
+      h = g;
+      g = f;
+      f = e;
+      e = (d + t1) | 0;
+      d = c;
+      c = b;
+      b = a;
+      a = (t1 + t2) | 0;
+    }
+
+    const s = this.state;
+    s[0] += a;
+    s[1] += b;
+    s[2] += c;
+    s[3] += d;
+    s[4] += e;
+    s[5] += f;
+    s[6] += g;
+    s[7] += h;
+  }
+}
+
+const checkAborted = (signal: AbortSignal) => {
+  if (!signal.aborted) return;
+
+  const err = new Error("hashing aborted") as UploadRequestError;
+  err.aborted = true;
+  throw err;
+};
+
+/**
+ * 分段读取并计算文件的 SHA-256。
+ *
+ * `signal` 中断时抛出 `aborted` 为 `true` 的 {@link UploadRequestError}。
+ */
+export const hashFile = async (file: Blob, signal: AbortSignal) => {
+  const sha = new Sha256();
+
+  for (let offset = 0; offset < file.size; offset += READ_SIZE) {
+    checkAborted(signal);
+    const buf = await file.slice(offset, offset + READ_SIZE).arrayBuffer();
+    sha.update(new Uint8Array(buf));
+  }
+
+  checkAborted(signal);
+  return sha.hex();
+};
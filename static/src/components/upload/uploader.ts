@@ -0,0 +1,199 @@
+import request from "./request";
+import asyncPool from "./asyncPool";
+import { hashFile } from "./sha256";
+
+/** 网络中断后重试的最大次数 */
+const MAX_RETRIES = 5;
+/** 第 n 次重试前等待 n 倍的此时长（毫秒） */
+const RETRY_DELAY = 1000;
+
+/** 超过此大小的文件分块并发上传 */
+const CHUNK_THRESHOLD = 16 * 1024 * 1024;
+/** 分块大小 */
+const CHUNK_SIZE = 4 * 1024 * 1024;
+/** 同一文件同时上传的分块数 */
+const CHUNK_CONCURRENCY = 3;
+
+/** 服务端根据此请求头校验接收完整的文件 */
+const CONTENT_SHA256 = "x-content-sha256";
+
+export interface UploadContext {
+  action: string;
+  signal: AbortSignal;
+  /** 参数为已上传的字节数和速度（MB/s） */
+  onProgress: (uploaded: number, speed: number) => void;
+}
+
+const sleep = (ms: number) => new Promise((r) => setTimeout(r, ms));
+
+/**
+ * 网络中断（0）、续传位置错误（412）或文件仍被中断前的请求占用（423）时可以重试，
+ * 文件名冲突（409）等其他错误直接显示
+ */
+const retryable = (e: UploadRequestError) =>
+  !e.aborted && (e.status === 0 || e.status === 412 || e.status === 423);
+
+/** 从文件夹中选择的文件使用相对路径，服务端据此还原文件夹结构 */
+const fileQuery = (file: File) =>
+  file.webkitRelativePath
+    ? "path=" + encodeURIComponent(file.webkitRelativePath)
+    : "name=" + encodeURIComponent(file.name);
+
+/** 按本次开始上传后的字节数计算速度，续传时不计入之前已上传的部分 */
+const speedMeter = () => {
+  let start: { time: number; uploaded: number } | undefined;
+
+  return (uploaded: number) => {
+    const now = Date.now();
+    if (!start) {
+      start = { time: now, uploaded };
+      return 0;
+    }
+
+    const seconds = (now - start.time) / 1000;
+    return seconds ? (uploaded - start.uploaded) / 1024 / 1024 / seconds : 0;
+  };
+};
+
+/** 查询服务端已接收的字节数 */
+const queryOffset = async (file: File, ctx: UploadContext) => {
+  const xhr = await request({
+    method: "HEAD",
+    url: `${ctx.action}?${fileQuery(file)}`,
+    headers: { "Upload-Length": String(file.size) },
+    signal: ctx.signal,
+  });
+
+  return Number(xhr.getResponseHeader("Upload-Offset") ?? 0);
+};
+
+/** 失败时重试 `task`，网络中断以外的错误直接抛出 */
+const withRetry = async (task: () => Promise<void>) => {
+  for (let attempt = 0; ; attempt++) {
+    try {
+      return await task();
+    } catch (e) {
+      const err = e as UploadRequestError;
+      if (!retryable(err) || attempt >= MAX_RETRIES) throw err;
+
+      await sleep(RETRY_DELAY * (attempt + 1));
+    }
+  }
+};
+
+/**
+ * 将文件分块并发上传，失败的分块单独重试。
+ *
+ * 任一分块最终失败时中断其他分块。哈希与分块上传同时计算，
+ * 随最后一个分块发送，服务端在全部分块接收完成后才校验。
+ */
+const uploadChunks = async (file: File, ctx: UploadContext) => {
+  const speed = speedMeter();
+  const hash = hashFile(file, ctx.signal);
+  // 分块失败时不再等待哈希
+  hash.catch(() => {});
+
+  const chunks: { first: number; last: number }[] = [];
+  for (let first = 0; first < file.size; first += CHUNK_SIZE) {
+    chunks.push({ first, last: Math.min(first + CHUNK_SIZE, file.size) - 1 });
+  }
+
+  // 每个分块已上传的字节数
+  const loaded = new Map<number, number>();
+  const report = () => {
+    let uploaded = 0;
+    loaded.forEach((n) => {
+      uploaded += n;
+    });
+    ctx.onProgress(uploaded, speed(uploaded));
+  };
+
+  // 一个分块失败后中断其他分块
+  const controller = new AbortController();
+  const abort = () => controller.abort();
+  ctx.signal.addEventListener("abort", abort);
+
+  // 最先失败的分块的错误，其他分块随后因中断而失败
+  let failure: UploadRequestError | undefined;
+
+  try {
+    await asyncPool(CHUNK_CONCURRENCY, chunks, ({ first, last }) =>
+      withRetry(async () => {
+        const headers: Record<string, string> = {
+          "Content-Range": `bytes ${first}-${last}/${file.size}`,
+        };
+        if (last === file.size - 1) {
+          headers[CONTENT_SHA256] = await hash;
+        }
+
+        try {
+          await request({
+            method: "PUT",
+            url: `${ctx.action}/chunk?${fileQuery(file)}`,
+            headers,
+            body: file.slice(first, last + 1),
+            onProgress: (n) => {
+              loaded.set(first, n);
+              report();
+            },
+            signal: controller.signal,
+          });
+
+          loaded.set(first, last - first + 1);
+        } catch (e) {
+          loaded.delete(first);
+          throw e;
+        } finally {
+          report();
+        }
+      }).catch((e) => {
+        failure ??= e;
+        controller.abort();
+        throw e;
+      }),
+    );
+  } catch (e) {
+    throw failure ?? e;
+  } finally {
+    ctx.signal.removeEventListener("abort", abort);
+  }
+};
+
+/**
+ * 上传一个文件，连接中断后查询服务端已接收的字节数并从该处继续上传。
+ *
+ * 上传前先计算文件的哈希，服务端接收完成后据此校验。
+ * 服务端拒绝上传或被主动中断时抛出 {@link UploadRequestError}。
+ */
+const uploadWhole = async (file: File, ctx: UploadContext) => {
+  const speed = speedMeter();
+  const hash = await hashFile(file, ctx.signal);
+
+  await withRetry(async () => {
+    // 第一次上传前也查询，刷新页面后可以继续之前未完成的上传
+    const offset = await queryOffset(file, ctx);
+
+    await request({
+      method: offset > 0 ? "PATCH" : "POST",
+      url: `${ctx.action}?${fileQuery(file)}`,
+      headers:
+        offset > 0
+          ? {
+              [CONTENT_SHA256]: hash,
+              "Upload-Offset": String(offset),
+              "Upload-Length": String(file.size),
+            }
+          : { [CONTENT_SHA256]: hash },
+      body: file.slice(offset),
+      onProgress: (loaded) =>
+        ctx.onProgress(offset + loaded, speed(offset + loaded)),
+      signal: ctx.signal,
+    });
+  });
+};
+
+/** 大文件分块并发上传，其他文件整体上传 */
+const uploadFile = (file: File, ctx: UploadContext) =>
+  file.size > CHUNK_THRESHOLD ? uploadChunks(file, ctx) : uploadWhole(file, ctx);
+
+export default uploadFile;
@@ -3,6 +3,8 @@ import type { Locale } from ".";
 const EN_US: Locale = {
   invalid_request: "Invalid request",
   mode_is_required: "Query parameter 'mode' is required",
+  certificate_fingerprint_label:
+    "Certificate fingerprint, should match the one shown below the QR code on the computer",
 
   file_item_file_size_label: "Size",
   file_item_file_type_label: "Type",
@@ -13,12 +15,14 @@ const EN_US: Locale = {
     "Click the plus button in the top left corner to select files",
   send_page_uploading_tooltip:
     "Click the red button on the right to interrupt unfinished tasks",
+  upload_failed: "Upload failed, please check the network and try again",
 
   receive_page_title: "Receive File",
   receive_page_toast:
     "Do not refresh this page, or the file list will be cleared",
   receive_page_file_list_header:
     "Click the filename or the button on the right to download",
+  receive_page_download_all: "Download all as ZIP",
 };
 
 export default EN_US;
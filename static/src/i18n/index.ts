@@ -4,6 +4,7 @@ import ZH_CN from "./zh_cn";
 export interface Locale {
   invalid_request: string;
   mode_is_required: string;
+  certificate_fingerprint_label: string;
 
   file_item_file_size_label: string;
   file_item_file_type_label: string;
@@ -12,10 +13,12 @@ export interface Locale {
   send_page_empty_title: string;
   send_page_empty_description: string;
   send_page_uploading_tooltip: string;
+  upload_failed: string;
 
   receive_page_title: string;
   receive_page_toast: string;
   receive_page_file_list_header: string;
+  receive_page_download_all: string;
 }
 
 export const getLocale = (): Locale => {
@@ -3,6 +3,7 @@ import type { Locale } from ".";
 const ZH_CN: Locale = {
   invalid_request: "无效的请求",
   mode_is_required: "缺少查询参数：mode",
+  certificate_fingerprint_label: "证书指纹，应与电脑端二维码下方显示的一致",
 
   file_item_file_size_label: "大小",
   file_item_file_type_label: "类型",
@@ -11,10 +12,12 @@ const ZH_CN: Locale = {
   send_page_empty_title: "未选择文件",
   send_page_empty_description: "点击左上角的加号按钮选择文件",
   send_page_uploading_tooltip: "未完成的任务可点击右侧红色按钮中断",
+  upload_failed: "上传失败，请检查网络后重新上传",
 
   receive_page_title: "接收文件",
   receive_page_toast: "不要刷新此页面，否则文件列表将会被清空",
   receive_page_file_list_header: "点击文件名或右侧按钮即可下载",
+  receive_page_download_all: "打包下载全部文件",
 };
 
 export default ZH_CN;
@@ -1,5 +1,16 @@
-import { Match, Show, Switch, createResource, useContext } from "solid-js";
-import { AiOutlineCloudDownload } from "solid-icons/ai";
+import {
+  For,
+  Match,
+  Show,
+  Switch,
+  createResource,
+  useContext,
+} from "solid-js";
+import {
+  AiOutlineCloudDownload,
+  AiOutlineFileZip,
+  AiOutlineFolder,
+} from "solid-icons/ai";
 import Result from "~/components/result";
 import SpinLoading from "~/components/loading/spin";
 import Space from "~/components/space";
@@ -11,7 +22,7 @@ import "./index.scss";
 import LocaleContext from "~/context";
 import ZH_CN from "~/i18n/zh_cn";
 
-type ResponseData = SendFile[] | BadRequest;
+type ResponseData = PhoneFile[] | BadRequest;
 
 const fetchData = async (): Promise<ResponseData> => {
   const response = await fetch("/files");
@@ -21,10 +32,38 @@ const fetchData = async (): Promise<ResponseData> => {
     return msg;
   }
 
-  const body: SendFile[] = await response.json();
+  const body: PhoneFile[] = await response.json();
   return body;
 };
 
+interface FolderGroup {
+  /** 文件所在文件夹的相对路径，不在文件夹中时为空 */
+  folder: string;
+  files: PhoneFile[];
+}
+
+/** 按 `relative_path` 将文件分组到所在的文件夹，不在文件夹中的文件排在最前 */
+const groupByFolder = (files: PhoneFile[]): FolderGroup[] => {
+  const groups = new Map<string, PhoneFile[]>();
+
+  for (const file of files) {
+    const path = file.relative_path || file.name;
+    const slash = path.lastIndexOf("/");
+    const folder = slash === -1 ? "" : path.slice(0, slash);
+
+    const group = groups.get(folder);
+    if (group) {
+      group.push(file);
+    } else {
+      groups.set(folder, [file]);
+    }
+  }
+
+  return Array.from(groups, ([folder, files]) => ({ folder, files })).sort(
+    (a, b) => a.folder.localeCompare(b.folder),
+  );
+};
+
 const Receive = () => {
   const locale = useContext(LocaleContext)!;
 
@@ -53,53 +92,75 @@ const Receive = () => {
           </div>
         </Match>
 
-        <Match when={(data() as SendFile[]).length}>
+        <Match when={(data() as PhoneFile[]).length}>
           <div class="content">
             <Toast message={locale.receive_page_toast} duration={3000} />
 
-            <List
-              class="receive-file-list"
-              header={locale.receive_page_file_list_header}
-              dataSource={data() as SendFile[]}
-              renderItem={(item, index) => {
-                const url = "/download/" + encodeURIComponent(item.path);
-                return (
-                  <List.Item
-                    title={
-                      <span class="filename">
-                        <span class="label">{index() + 1}.</span>
-                        <Link
-                          download={item.name}
-                          href={url}
-                          class="download-url"
-                        >
-                          {item.name}
-                        </Link>
-                      </span>
-                    }
-                    description={
-                      <Space gap={12} class="file-description">
-                        <span>
-                          {locale.file_item_file_size_label}：{item.size}
-                        </span>
-                        <Show when={locale === ZH_CN}>
-                          <span>类型：{fileType(item.extension)}</span>
-                        </Show>
-                      </Space>
-                    }
-                    extra={[
-                      <Link
-                        download={item.name}
-                        href={url}
-                        class="download-icon"
-                      >
-                        <AiOutlineCloudDownload />
-                      </Link>,
-                    ]}
-                  />
-                );
-              }}
-            />
+            {/* 手机浏览器无法批量下载，由服务端将所有文件打包为一个 ZIP */}
+            <Link href="/download-all" class="download-all">
+              <AiOutlineFileZip />
+              <span>{locale.receive_page_download_all}</span>
+            </Link>
+
+            <For each={groupByFolder(data() as PhoneFile[])}>
+              {(group, index) => (
+                <List
+                  class="receive-file-list"
+                  header={
+                    <>
+                      <Show when={index() === 0}>
+                        <div>{locale.receive_page_file_list_header}</div>
+                      </Show>
+                      <Show when={group.folder}>
+                        <div class="folder">
+                          <AiOutlineFolder />
+                          <span>{group.folder}</span>
+                        </div>
+                      </Show>
+                    </>
+                  }
+                  dataSource={group.files}
+                  renderItem={(item, i) => {
+                    const url = "/download/" + encodeURIComponent(item.id);
+                    return (
+                      <List.Item
+                        title={
+                          <span class="filename">
+                            <span class="label">{i() + 1}.</span>
+                            <Link
+                              download={item.name}
+                              href={url}
+                              class="download-url"
+                            >
+                              {item.name}
+                            </Link>
+                          </span>
+                        }
+                        description={
+                          <Space gap={12} class="file-description">
+                            <span>
+                              {locale.file_item_file_size_label}：{item.size}
+                            </span>
+                            <Show when={locale === ZH_CN}>
+                              <span>类型：{fileType(item.extension)}</span>
+                            </Show>
+                          </Space>
+                        }
+                        extra={[
+                          <Link
+                            download={item.name}
+                            href={url}
+                            class="download-icon"
+                          >
+                            <AiOutlineCloudDownload />
+                          </Link>,
+                        ]}
+                      />
+                    );
+                  }}
+                />
+              )}
+            </For>
           </div>
         </Match>
       </Switch>
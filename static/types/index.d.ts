@@ -1,12 +1,7 @@
-interface RequestTask {
-  id: string;
-  xhr: XMLHttpRequest;
-  data: File;
-  start?: number;
-  done?: () => void;
-}
-
 interface BadRequest {
   error: string;
   advice: string | null;
 }
+
+/** 手机端接收的文件信息，不包含文件在电脑上的路径 */
+type PhoneFile = Omit<SendFile, "path">;
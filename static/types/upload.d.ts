@@ -3,27 +3,28 @@ interface FileListItem {
   file: File;
   speed?: number;
   percent?: number;
+  /// 服务端拒绝或中断上传时返回的错误
+  error?: BadRequest;
 }
 
-interface UploadRequestError extends Error, ProgressEvent<EventTarget> {
+interface UploadRequestError extends Error {
   status?: number;
-  method?: UploadRequestOption["method"];
+  method?: RequestOption["method"];
   url?: string;
-  name?: string;
-  message?: string;
+  name: string;
+  message: string;
+  /// 服务端返回的响应体
+  body?: object | string;
+  /// 请求被主动中断
+  aborted?: boolean;
 }
 
-interface UploadProgressEvent extends ProgressEvent<EventTarget> {
-  percent?: number;
-  speed?: number;
-}
-
-interface UploadRequestOption extends FileListItem {
-  action: string;
-  method: "POST" | "PUT";
-  onProgress: (e: UploadProgressEvent, file: File) => void;
-  onError: (e: UploadRequestError, body?: object | string) => void;
-  onSuccess: (body: object | string, xhr: XMLHttpRequest) => void;
-  withCredentials?: boolean;
+interface RequestOption {
+  method: "HEAD" | "POST" | "PATCH" | "PUT";
+  url: string;
   headers?: Record<string, string>;
+  body?: Blob;
+  /// 参数为已上传的字节数
+  onProgress?: (loaded: number) => void;
+  signal?: AbortSignal;
 }
@@ -1,25 +1,48 @@
 interface TaskMessage {
+  id: number;
   path: string;
   name: string;
   percent: number;
+  /** 最近一段时间内的速度，MB/s */
   speed: number;
+  /** 平均速度，MB/s */
+  average_speed: number;
+  transferred: number;
+  total: number;
+  /** 预计剩余时间（秒） */
+  eta: number | null;
   size: string;
   aborted: boolean;
+  hash: string | null;
+  folder: string | null;
 }
 
 interface QrCode {
   svg: string;
   url: string;
   id: number;
+  fingerprint: string | null;
 }
 
 interface SendFile {
+  id: string;
   name: string;
   path: string;
+  relative_path: string;
   extension: string;
   size: string;
 }
 
+interface ApprovalRequest {
+  id: number;
+  session: number;
+  name: string;
+  folder: string | null;
+  size: string;
+  client: string | null;
+  device: string | null;
+}
+
 type CSSProperties = JSX.CSSProperties;
 
 interface Translations {
@@ -38,11 +61,16 @@ interface Translations {
   qrcode_page_url_tooltip: string;
   qrcode_page_url_copied_message: string;
   qrcode_page_toast_message: string;
+  qrcode_page_fingerprint_label: string;
   ok_button_text: string;
   clear_button_text: string;
   send_page_title: string;
   send_page_empty_drop_description: string;
   send_page_drop_description: string;
+  send_page_connected_description: string;
+  send_page_new_qrcode_button_text: string;
+  send_page_download_completed_label: string;
+  send_page_download_interrupted_label: string;
   list_item_file_size_label: string;
   list_item_file_type_label: string;
   send_page_list_item_tooltip: string;
@@ -52,4 +80,12 @@ interface Translations {
   receive_page_dropdown_pick_button_label: string;
   receive_page_directory_path_label: string;
   receive_page_directory_path_tooltip: string;
+  receive_page_pause_tooltip: string;
+  receive_page_resume_tooltip: string;
+  receive_page_cancel_tooltip: string;
+  approval_dialog_title: string;
+  approval_dialog_sender_label: string;
+  approval_accept_button_text: string;
+  approval_always_allow_button_text: string;
+  approval_reject_button_text: string;
 }